@@ -14,6 +14,11 @@ pub struct DeleteOAuth2ClientCommand {
     pub id: i64,
 }
 
+pub struct RotateOAuth2ClientSecretCommand {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
 pub struct VerifyOAuth2ClientCommand<'a> {
     pub client_id: &'a str,
     pub client_secret: &'a str,
@@ -0,0 +1,91 @@
+//! `[modules.oauth2_server]`, read with
+//! `Config::module::<OAuth2ServerConfig>(NAME)`.
+use stardust::FieldError;
+
+pub const NAME: &str = "oauth2_server";
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct OAuth2ServerConfig {
+    pub auth_code_ttl_secs: u32, // to exchange the code for tokens
+    pub access_token_ttl_secs: u32,
+    pub refresh_token_ttl_secs: u32, // restarted by every refresh
+}
+
+impl Default for OAuth2ServerConfig {
+    fn default() -> Self {
+        Self {
+            auth_code_ttl_secs: 10 * 60,
+            access_token_ttl_secs: 24 * 60 * 60,
+            refresh_token_ttl_secs: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl OAuth2ServerConfig {
+    pub fn auth_code_ttl(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.auth_code_ttl_secs.into())
+    }
+
+    pub fn access_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.access_token_ttl_secs.into())
+    }
+
+    pub fn refresh_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.refresh_token_ttl_secs.into())
+    }
+}
+
+impl stardust::config::ModuleConfig for OAuth2ServerConfig {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        for (field, ttl) in [
+            ("auth_code_ttl_secs", self.auth_code_ttl_secs),
+            ("access_token_ttl_secs", self.access_token_ttl_secs),
+        ] {
+            if ttl == 0 {
+                errors.push(FieldError::new(
+                    field,
+                    "out_of_range",
+                    "must be at least 1",
+                ));
+            }
+        }
+        if self.refresh_token_ttl_secs < self.access_token_ttl_secs {
+            errors.push(FieldError::new(
+                "refresh_token_ttl_secs",
+                "out_of_range",
+                "must not be shorter than access_token_ttl_secs",
+            ));
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_module() {
+        let mut config = stardust::config::Config::test_config();
+        config.modules.insert(
+            super::NAME.into(),
+            serde_json::json!({ "access_token_ttl_secs": 3600 }),
+        );
+        let oauth2 =
+            config.module::<super::OAuth2ServerConfig>(super::NAME).unwrap();
+        assert_eq!(oauth2.access_token_ttl(), chrono::Duration::hours(1));
+        assert_eq!(oauth2.refresh_token_ttl(), chrono::Duration::days(30));
+
+        config.modules.insert(
+            super::NAME.into(),
+            serde_json::json!({ "refresh_token_ttl_secs": 60 }),
+        );
+        let error = config
+            .module::<super::OAuth2ServerConfig>(super::NAME)
+            .unwrap_err();
+        assert_eq!(
+            error.fields()[0].field,
+            "modules.oauth2_server.refresh_token_ttl_secs"
+        );
+    }
+}
@@ -62,23 +62,28 @@ impl OAuth2AuthorizationEntity {
         &mut self,
         access_token: String,
         refresh_token_hash: String,
+        config: &crate::config::OAuth2ServerConfig,
     ) {
         let now = chrono::Utc::now();
         self.auth_code_expires_at = now;
         self.access_token_value = access_token;
         self.access_token_issued_at = now;
-        self.access_token_expires_at = now + chrono::Duration::days(1);
+        self.access_token_expires_at = now + config.access_token_ttl();
         self.refresh_token_hash = refresh_token_hash;
         self.refresh_token_issued_at = now;
-        self.refresh_token_expires_at = now + chrono::Duration::days(30);
+        self.refresh_token_expires_at = now + config.refresh_token_ttl();
     }
 
-    pub fn refresh_token(&mut self, access_token: String) {
+    pub fn refresh_token(
+        &mut self,
+        access_token: String,
+        config: &crate::config::OAuth2ServerConfig,
+    ) {
         let now = chrono::Utc::now();
         self.access_token_value = access_token;
         self.access_token_issued_at = now;
-        self.access_token_expires_at = now + chrono::Duration::days(1);
-        self.refresh_token_expires_at = now + chrono::Duration::days(30);
+        self.access_token_expires_at = now + config.access_token_ttl();
+        self.refresh_token_expires_at = now + config.refresh_token_ttl();
     }
 }
 
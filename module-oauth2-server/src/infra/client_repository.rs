@@ -69,6 +69,25 @@ pub async fn delete_client(
     Ok(())
 }
 
+pub async fn save_client_secret(
+    handle: &mut postgres::Handle<'_>,
+    entity: &entity::OAuth2ClientEntity,
+) -> stardust::Result<entity::OAuth2ClientEntity> {
+    let mut querybuilder = sqlx::QueryBuilder::new(
+        "UPDATE oauth2_client SET client_secret_hash = ",
+    );
+    querybuilder.push_bind(&entity.client_secret_hash);
+    querybuilder.push(" WHERE id = ");
+    querybuilder.push_bind(entity.id);
+    querybuilder.push(" RETURNING *");
+    let row = querybuilder
+        .build_query_as::<model::OAuth2ClientModel>()
+        .fetch_one(handle.executor())
+        .await
+        .map_err(stardust::database::internal::into_error)?;
+    Ok(row.into())
+}
+
 pub struct PostgresClientRepository {}
 
 impl PostgresClientRepository {
@@ -104,4 +123,12 @@ impl crate::repository::ClientRepository for PostgresClientRepository {
     ) -> stardust::Result<()> {
         delete_client(handle, command).await
     }
+
+    async fn save_client_secret(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &entity::OAuth2ClientEntity,
+    ) -> stardust::Result<entity::OAuth2ClientEntity> {
+        save_client_secret(handle, entity).await
+    }
 }
@@ -1,17 +1,13 @@
-use stardust::database::{Database, Handle};
+use stardust::database::internal::postgres;
+use stardust::infra::migration::{Migrations, SqlMigration};
 
-pub async fn migrate(
-    database: stardust::infra::migration::Database,
-) -> stardust::Result<()> {
-    const NAME: &str = "oauth2_server_migration";
+pub const NAME: &str = "oauth2_server_migration";
 
-    let mut migration =
-        stardust::infra::migration::get_latest(&mut database.handle(), NAME)
-            .await?
-            .unwrap_or_default();
-    if migration.version == 0 {
-        let mut handle = database.tx_handle().await?;
-        sqlx::query(
+pub fn migrations() -> Migrations<postgres::Database> {
+    Migrations::new(NAME).register(SqlMigration {
+        version: 1,
+        description: "create oauth2_client and oauth2_authorization tables",
+        statements: &[
             r#" CREATE TABLE IF NOT EXISTS oauth2_client (
                 id BIGSERIAL PRIMARY KEY,
                 client_id VARCHAR(255) UNIQUE NOT NULL,
@@ -24,13 +20,7 @@ pub async fn migrate(
                 token_settings JSONB NOT NULL,
                 created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
             ); "#,
-        )
-        .execute(handle.executor())
-        .await
-        .map_err(stardust::database::internal::into_error)?;
-
-        sqlx::query(
-             r#" CREATE TABLE IF NOT EXISTS oauth2_authorization (
+            r#" CREATE TABLE IF NOT EXISTS oauth2_authorization (
                 id BIGSERIAL PRIMARY KEY,
                 oauth2_client_id BIGSERIAL NOT NULL,
                 principal_id BIGINT NOT NULL,
@@ -49,19 +39,14 @@ pub async fn migrate(
                 config JSONB NOT NULL,
                 CONSTRAINT fk_oauth2_client_id FOREIGN KEY (oauth2_client_id) REFERENCES oauth2_client(id)
             ); "#,
-        )
-        .execute(handle.executor())
-        .await
-        .map_err(stardust::database::internal::into_error)?;
+        ],
+        down: Some(&[
+            "DROP TABLE IF EXISTS oauth2_authorization",
+            "DROP TABLE IF EXISTS oauth2_client",
+        ]),
+    })
+}
 
-        migration.name = NAME.into();
-        migration.version = 1;
-        migration.description =
-            "create oauth2_client and oauth2_authorization tables".into();
-        migration =
-            stardust::infra::migration::save(&mut handle, &migration).await?;
-        handle.commit().await?;
-    }
-    if migration.version == 1 {}
-    Ok(())
+pub async fn migrate(database: postgres::Database) -> stardust::Result<()> {
+    migrations().run(&database).await
 }
@@ -3,3 +3,5 @@ pub mod client_repository;
 pub mod migration;
 pub mod migration_repository;
 pub mod model;
+pub mod mysql;
+pub mod sqlite;
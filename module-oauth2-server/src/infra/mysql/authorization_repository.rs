@@ -0,0 +1,238 @@
+use stardust::database::internal::mysql;
+
+use crate::{
+    entity,
+    infra::model::{self, OAuth2AuthorizationModel},
+    query,
+};
+
+pub async fn create_authorization(
+    handle: &mut mysql::Handle<'_>,
+    entity: &crate::entity::OAuth2AuthorizationEntity,
+) -> stardust::Result<crate::entity::OAuth2AuthorizationEntity> {
+    let mut builder = sqlx::QueryBuilder::new(
+        r#"INSERT INTO oauth2_authorization
+            (oauth2_client_id, principal_id, grant_type, scopes, state,
+            auth_code_value, auth_code_issued_at, auth_code_expires_at,
+            access_token_value, access_token_issued_at, access_token_expires_at,
+            refresh_token_hash, refresh_token_issued_at, refresh_token_expires_at, config) "#,
+    );
+    builder.push_values(std::iter::once(entity), |mut values, v| {
+        values
+            .push_bind(v.oauth2_client_id)
+            .push_bind(v.principal_id)
+            .push_bind(&v.grant_type)
+            .push_bind(&v.scope)
+            .push_bind(&v.state)
+            .push_bind(&v.auth_code_value)
+            .push_bind(v.auth_code_issued_at)
+            .push_bind(v.auth_code_expires_at)
+            .push_bind(&v.access_token_value)
+            .push_bind(v.access_token_issued_at)
+            .push_bind(v.access_token_expires_at)
+            .push_bind(&v.refresh_token_hash)
+            .push_bind(v.refresh_token_issued_at)
+            .push_bind(v.refresh_token_expires_at)
+            .push_bind(&v.config);
+    });
+    let result = builder
+        .build()
+        .execute(handle.executor())
+        .await
+        .map_err(stardust::database::internal::into_error)?;
+    // mysql has no RETURNING, the row is read back by its generated id
+    get_authorization(handle, result.last_insert_id() as i64).await
+}
+
+pub async fn find_authorization(
+    handle: &mut mysql::Handle<'_>,
+    query: &query::FindOAuth2AuthorizationQuery<'_>,
+) -> stardust::Result<Option<entity::OAuth2AuthorizationEntity>> {
+    let mut builder = sqlx::QueryBuilder::new(
+        r#"SELECT * FROM oauth2_authorization WHERE 1=1 "#,
+    );
+    if let Some(auth_code_value) = &query.auth_code_value {
+        builder.push(" AND auth_code_value = ");
+        builder.push_bind(auth_code_value);
+    }
+
+    if let Some(refresh_token_hash) = &query.refresh_token_hash {
+        builder.push(" AND refresh_token_hash = ");
+        builder.push_bind(refresh_token_hash);
+    }
+
+    if let Some(access_token) = &query.access_token {
+        builder.push(" AND access_token_value = ");
+        builder.push_bind(access_token);
+    }
+
+    let row = builder
+        .build_query_as::<model::OAuth2AuthorizationModel>()
+        .fetch_optional(handle.executor())
+        .await
+        .map_err(stardust::database::internal::into_error)?;
+    Ok(row.map(Into::into))
+}
+
+pub async fn save_authorization(
+    handle: &mut mysql::Handle<'_>,
+    entity: &entity::OAuth2AuthorizationEntity,
+) -> stardust::Result<entity::OAuth2AuthorizationEntity> {
+    let mut builder =
+        sqlx::QueryBuilder::new("UPDATE oauth2_authorization SET ");
+    builder
+        .push(" auth_code_expires_at = ")
+        .push_bind(entity.auth_code_expires_at)
+        .push(", access_token_value = ")
+        .push_bind(&entity.access_token_value)
+        .push(", access_token_issued_at = ")
+        .push_bind(entity.access_token_issued_at)
+        .push(", access_token_expires_at = ")
+        .push_bind(entity.access_token_expires_at)
+        .push(", refresh_token_hash = ")
+        .push_bind(&entity.refresh_token_hash)
+        .push(", refresh_token_issued_at = ")
+        .push_bind(entity.refresh_token_issued_at)
+        .push(", refresh_token_expires_at = ")
+        .push_bind(entity.refresh_token_expires_at)
+        .push(" WHERE id = ")
+        .push_bind(entity.id);
+    builder
+        .build()
+        .execute(handle.executor())
+        .await
+        .map_err(stardust::database::internal::into_error)?;
+    get_authorization(handle, entity.id).await
+}
+
+async fn get_authorization(
+    handle: &mut mysql::Handle<'_>,
+    id: i64,
+) -> stardust::Result<entity::OAuth2AuthorizationEntity> {
+    let row = sqlx::QueryBuilder::new(
+        "SELECT * FROM oauth2_authorization WHERE id = ",
+    )
+    .push_bind(id)
+    .build_query_as::<OAuth2AuthorizationModel>()
+    .fetch_one(handle.executor())
+    .await
+    .map_err(stardust::database::internal::into_error)?;
+    Ok(row.into())
+}
+
+pub async fn find_user(
+    handle: &mut mysql::Handle<'_>,
+    query: &query::FindOAuth2UserQuery<'_>,
+) -> stardust::Result<Option<entity::OAuthUserAggregate>> {
+    let mut builder = sqlx::QueryBuilder::new(
+        r#"
+        select
+            JSON_OBJECT(
+                'id', c.id,
+                'client_id', c.client_id,
+                'client_secret_hash', c.client_secret_hash,
+                'name', c.name,
+                'redirect_uris', c.redirect_uris,
+                'grant_types', c.grant_types,
+                'auth_methods', c.auth_methods,
+                'scopes', c.scopes
+            ) as client_json,
+            JSON_OBJECT(
+                'id', u.id,
+                'username', u.username,
+                'email', u.email,
+                'role', u.role,
+                'status', u.status,
+                'created_at', DATE_FORMAT(u.created_at, '%Y-%m-%dT%H:%i:%s.%fZ'),
+                'updated_at', DATE_FORMAT(u.updated_at, '%Y-%m-%dT%H:%i:%s.%fZ')
+            ) as user_json,
+            JSON_OBJECT(
+                'id', oa.id,
+                'oauth2_client_id', oa.oauth2_client_id,
+                'principal_id', oa.principal_id,
+                'grant_type', oa.grant_type,
+                'scopes', oa.scopes,
+                'state', oa.state,
+                'auth_code_value', oa.auth_code_value,
+                'auth_code_issued_at', DATE_FORMAT(oa.auth_code_issued_at, '%Y-%m-%dT%H:%i:%s.%fZ'),
+                'auth_code_expires_at', DATE_FORMAT(oa.auth_code_expires_at, '%Y-%m-%dT%H:%i:%s.%fZ'),
+                'access_token_value', oa.access_token_value,
+                'access_token_issued_at', DATE_FORMAT(oa.access_token_issued_at, '%Y-%m-%dT%H:%i:%s.%fZ'),
+                'access_token_expires_at', DATE_FORMAT(oa.access_token_expires_at, '%Y-%m-%dT%H:%i:%s.%fZ'),
+                'refresh_token_hash', oa.refresh_token_hash,
+                'refresh_token_issued_at', DATE_FORMAT(oa.refresh_token_issued_at, '%Y-%m-%dT%H:%i:%s.%fZ'),
+                'refresh_token_expires_at', DATE_FORMAT(oa.refresh_token_expires_at, '%Y-%m-%dT%H:%i:%s.%fZ'),
+                'config', oa.config
+            ) as authorization_json
+        from oauth2_authorization oa
+        left join stardust_user u on oa.principal_id = u.id
+        left join oauth2_client c on oa.oauth2_client_id = c.id
+        where oa.access_token_value =
+    "#,
+    );
+    builder.push_bind(query.access_token);
+
+    let row = builder
+        .build_query_as::<model::OAuth2AuthorizationUserModel>()
+        .fetch_optional(handle.executor())
+        .await
+        .map_err(stardust::database::internal::into_error)?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    Ok(Some(entity::OAuthUserAggregate {
+        client: row.client.into(),
+        user: row.user.into(),
+        authorization: row.authorization.into(),
+    }))
+}
+
+#[derive(Default)]
+pub struct MySqlAuthorizationRepository {}
+
+impl MySqlAuthorizationRepository {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::repository::AuthorizationRepository
+    for MySqlAuthorizationRepository
+{
+    type Handle<'h> = mysql::Handle<'h>;
+
+    async fn create_authorization(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &crate::entity::OAuth2AuthorizationEntity,
+    ) -> stardust::Result<crate::entity::OAuth2AuthorizationEntity> {
+        create_authorization(handle, entity).await
+    }
+
+    async fn find_authorization(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        query: &query::FindOAuth2AuthorizationQuery<'_>,
+    ) -> stardust::Result<Option<entity::OAuth2AuthorizationEntity>> {
+        find_authorization(handle, query).await
+    }
+
+    async fn save_authorization(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &entity::OAuth2AuthorizationEntity,
+    ) -> stardust::Result<entity::OAuth2AuthorizationEntity> {
+        save_authorization(handle, entity).await
+    }
+
+    async fn find_user(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        query: &query::FindOAuth2UserQuery<'_>,
+    ) -> stardust::Result<Option<entity::OAuthUserAggregate>> {
+        find_user(handle, query).await
+    }
+}
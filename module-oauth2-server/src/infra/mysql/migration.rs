@@ -0,0 +1,52 @@
+use stardust::database::internal::mysql;
+use stardust::infra::migration::{Migrations, SqlMigration};
+
+// DDL commits implicitly on mysql, so the statements are written to be
+// rerun after a step fails halfway, see `stardust::infra::migration::mysql`
+pub fn migrations() -> Migrations<mysql::Database> {
+    Migrations::new(crate::infra::migration::NAME).register(SqlMigration {
+        version: 1,
+        description: "create oauth2_client and oauth2_authorization tables",
+        statements: &[
+            r#" CREATE TABLE IF NOT EXISTS oauth2_client (
+                id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                client_id VARCHAR(255) UNIQUE NOT NULL,
+                client_secret_hash VARCHAR(255) NOT NULL,
+                name VARCHAR(255) NOT NULL,
+                redirect_uris TEXT NOT NULL,
+                grant_types TEXT NOT NULL,
+                auth_methods TEXT NOT NULL,
+                scopes TEXT NOT NULL,
+                token_settings JSON NOT NULL,
+                created_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6)
+            ); "#,
+            r#" CREATE TABLE IF NOT EXISTS oauth2_authorization (
+                id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                oauth2_client_id BIGINT NOT NULL,
+                principal_id BIGINT NOT NULL,
+                grant_type VARCHAR(50) NOT NULL,
+                scopes TEXT,
+                state VARCHAR(255),
+                auth_code_value VARCHAR(255),
+                auth_code_issued_at DATETIME(6),
+                auth_code_expires_at DATETIME(6),
+                access_token_value VARCHAR(255),
+                access_token_issued_at DATETIME(6),
+                access_token_expires_at DATETIME(6),
+                refresh_token_hash VARCHAR(255),
+                refresh_token_issued_at DATETIME(6),
+                refresh_token_expires_at DATETIME(6),
+                config JSON NOT NULL,
+                CONSTRAINT fk_oauth2_client_id FOREIGN KEY (oauth2_client_id) REFERENCES oauth2_client(id)
+            ); "#,
+        ],
+        down: Some(&[
+            "DROP TABLE IF EXISTS oauth2_authorization",
+            "DROP TABLE IF EXISTS oauth2_client",
+        ]),
+    })
+}
+
+pub async fn migrate(database: mysql::Database) -> stardust::Result<()> {
+    migrations().run(&database).await
+}
@@ -0,0 +1,3 @@
+pub mod authorization_repository;
+pub mod client_repository;
+pub mod migration;
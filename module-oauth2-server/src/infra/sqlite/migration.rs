@@ -0,0 +1,50 @@
+use stardust::database::internal::sqlite;
+use stardust::infra::migration::{Migrations, SqlMigration};
+
+pub fn migrations() -> Migrations<sqlite::Database> {
+    Migrations::new(crate::infra::migration::NAME).register(SqlMigration {
+        version: 1,
+        description: "create oauth2_client and oauth2_authorization tables",
+        statements: &[
+            r#" CREATE TABLE IF NOT EXISTS oauth2_client (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                client_id TEXT UNIQUE NOT NULL,
+                client_secret_hash TEXT NOT NULL,
+                name TEXT NOT NULL,
+                redirect_uris TEXT NOT NULL,
+                grant_types TEXT NOT NULL,
+                auth_methods TEXT NOT NULL,
+                scopes TEXT NOT NULL,
+                token_settings TEXT NOT NULL,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            ); "#,
+            r#" CREATE TABLE IF NOT EXISTS oauth2_authorization (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                oauth2_client_id INTEGER NOT NULL,
+                principal_id INTEGER NOT NULL,
+                grant_type TEXT NOT NULL,
+                scopes TEXT,
+                state TEXT,
+                auth_code_value TEXT,
+                auth_code_issued_at DATETIME,
+                auth_code_expires_at DATETIME,
+                access_token_value TEXT,
+                access_token_issued_at DATETIME,
+                access_token_expires_at DATETIME,
+                refresh_token_hash TEXT,
+                refresh_token_issued_at DATETIME,
+                refresh_token_expires_at DATETIME,
+                config TEXT NOT NULL,
+                CONSTRAINT fk_oauth2_client_id FOREIGN KEY (oauth2_client_id) REFERENCES oauth2_client(id)
+            ); "#,
+        ],
+        down: Some(&[
+            "DROP TABLE IF EXISTS oauth2_authorization",
+            "DROP TABLE IF EXISTS oauth2_client",
+        ]),
+    })
+}
+
+pub async fn migrate(database: sqlite::Database) -> stardust::Result<()> {
+    migrations().run(&database).await
+}
@@ -0,0 +1,236 @@
+pub mod authorization_repository;
+pub mod client_repository;
+pub mod migration;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use module_user::service::UserService;
+    use stardust::database::internal::sqlite;
+
+    use crate::{
+        command, query,
+        service::{OAuth2AuthorizationService, OAuth2ClientService},
+    };
+
+    type UserRepository =
+        module_user::infra::sqlite::user_repository::SqliteUserRepository;
+    type ApiKeyRepository =
+        module_user::infra::sqlite::apikey_repository::SqliteApiKeyRepository;
+    type UserServiceImpl = module_user::internal::UserServiceImpl<
+        sqlite::Database,
+        UserRepository,
+        stardust::hash::NoOpHasher,
+    >;
+    type ApiKeyServiceImpl = module_user::internal::ApiKeyServiceImpl<
+        sqlite::Database,
+        ApiKeyRepository,
+        module_user::internal::ImmediateUsageTracker<
+            sqlite::Database,
+            ApiKeyRepository,
+        >,
+        stardust::hash::NoOpHasher,
+    >;
+    type ClientServiceImpl = crate::internal::OAuth2ClientServiceImpl<
+        sqlite::Database,
+        super::client_repository::SqliteClientRepository,
+        stardust::hash::NoOpHasher,
+    >;
+    type AuthorizationServiceImpl =
+        crate::internal::OAuth2AuthorizationServiceImpl<
+            sqlite::Database,
+            super::authorization_repository::SqliteAuthorizationRepository,
+            ClientServiceImpl,
+            stardust::hash::NoOpHasher,
+        >;
+
+    impl module_user::Container for TestContainer {
+        type UserService = UserServiceImpl;
+        type ApiKeyService = ApiKeyServiceImpl;
+
+        fn user_service(&self) -> Arc<Self::UserService> {
+            self.user_service.clone()
+        }
+        fn apikey_service(&self) -> Arc<Self::ApiKeyService> {
+            self.apikey_service.clone()
+        }
+    }
+
+    struct TestContainer {
+        user_service: Arc<UserServiceImpl>,
+        apikey_service: Arc<ApiKeyServiceImpl>,
+        client_service: Arc<ClientServiceImpl>,
+        authorization_service: Arc<AuthorizationServiceImpl>,
+    }
+
+    impl TestContainer {
+        fn new(database: sqlite::Database) -> Self {
+            let hasher = Arc::new(stardust::hash::NoOpHasher);
+            let apikey_repo = Arc::new(ApiKeyRepository::new());
+            let client_service = Arc::new(ClientServiceImpl::new(
+                database.clone(),
+                Arc::new(
+                    super::client_repository::SqliteClientRepository::new(),
+                ),
+                hasher.clone(),
+            ));
+            Self {
+                user_service: Arc::new(UserServiceImpl::new(
+                    database.clone(),
+                    Arc::new(UserRepository::new()),
+                    hasher.clone(),
+                )),
+                apikey_service: Arc::new(ApiKeyServiceImpl::new(
+                    database.clone(),
+                    apikey_repo.clone(),
+                    module_user::internal::ImmediateUsageTracker::new(
+                        database.clone(),
+                        apikey_repo,
+                    ),
+                    hasher.clone(),
+                )),
+                client_service: client_service.clone(),
+                authorization_service: Arc::new(AuthorizationServiceImpl::new(
+                    database,
+                    Arc::new(
+                        super::authorization_repository::SqliteAuthorizationRepository::new(),
+                    ),
+                    client_service,
+                    hasher,
+                )),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_repositories() {
+        let (_, container) =
+            stardust::testing::ContainerBuilder::new(TestContainer::new)
+                .migrate(|database, _| {
+                    module_user::infra::sqlite::migration::migrate(
+                        database,
+                        Arc::new(stardust::hash::NoOpHasher),
+                    )
+                })
+                .migrate(|database, _| super::migration::migrate(database))
+                .build()
+                .await
+                .unwrap();
+        let client_service = container.client_service.clone();
+        let authorization_service = container.authorization_service.clone();
+
+        let admin = container
+            .user_service
+            .login(&module_user::command::LoginCommand::Local {
+                email: "admin@stardust.io".into(),
+                password: "1qaz2wsx!".into(),
+            })
+            .await
+            .unwrap();
+
+        let client = client_service
+            .create_client(&command::CreateOAuth2ClientCommand {
+                name: "test".into(),
+                client_id: "test-client".into(),
+                client_secret: "test-secret".into(),
+                redirect_uris: vec!["http://localhost/callback".into()],
+                grant_types: vec!["authorization_code".into()],
+                auth_methods: vec!["client_secret_post".into()],
+                scopes: vec!["read".into()],
+            })
+            .await
+            .unwrap();
+        assert!(
+            client_service
+                .create_client(&command::CreateOAuth2ClientCommand {
+                    name: "test".into(),
+                    client_id: "test-client".into(),
+                    client_secret: "test-secret".into(),
+                    redirect_uris: vec![],
+                    grant_types: vec![],
+                    auth_methods: vec![],
+                    scopes: vec![],
+                })
+                .await
+                .is_err_and(|e| matches!(e, stardust::Error::AlreadyExists(_)))
+        );
+
+        let verify_command = command::VerifyOAuth2AuthorizationCommand {
+            response_type: "code",
+            client_id: "test-client",
+            redirect_uri: "http://localhost/callback",
+            scope: "read",
+            state: "state",
+        };
+        let authorization = authorization_service
+            .authorize(&command::AuthorizeOAuth2Command {
+                principal: &admin.user,
+                verify_command: &verify_command,
+                config: Some(serde_json::json!({ "nonce": "n" })),
+            })
+            .await
+            .unwrap();
+        assert_eq!(authorization.oauth2_client_id, client.id);
+
+        let token = authorization_service
+            .token(&command::TokenCommand {
+                grant_type: "authorization_code",
+                client_id: "test-client",
+                client_secret: "test-secret",
+                redirect_uri: "http://localhost/callback",
+                code: Some(&authorization.auth_code_value),
+                refresh_token: None,
+            })
+            .await
+            .unwrap();
+
+        let found = authorization_service
+            .find_user(&query::FindOAuth2UserQuery {
+                access_token: &token.access_token,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.user.id, admin.user.id);
+        assert_eq!(found.client.client_id, "test-client");
+        assert_eq!(found.authorization.config["nonce"], "n");
+
+        let refreshed = authorization_service
+            .token(&command::TokenCommand {
+                grant_type: "refresh_token",
+                client_id: "test-client",
+                client_secret: "test-secret",
+                redirect_uri: "http://localhost/callback",
+                code: None,
+                refresh_token: token.refresh_token.as_deref(),
+            })
+            .await
+            .unwrap();
+        assert_ne!(refreshed.access_token, token.access_token);
+
+        client_service
+            .rotate_secret(&command::RotateOAuth2ClientSecretCommand {
+                client_id: "test-client".into(),
+                client_secret: "rotated-secret".into(),
+            })
+            .await
+            .unwrap();
+        let refresh_command = command::TokenCommand {
+            grant_type: "refresh_token",
+            client_id: "test-client",
+            client_secret: "test-secret",
+            redirect_uri: "http://localhost/callback",
+            code: None,
+            refresh_token: refreshed.refresh_token.as_deref(),
+        };
+        assert!(authorization_service.token(&refresh_command).await.is_err());
+        authorization_service
+            .token(&command::TokenCommand {
+                client_secret: "rotated-secret",
+                ..refresh_command
+            })
+            .await
+            .unwrap();
+    }
+}
@@ -1,4 +1,6 @@
-use std::{borrow::Cow, sync::Arc};
+use std::sync::Arc;
+
+use stardust::ErrorDetail;
 
 use crate::{command, entity, query, service};
 
@@ -6,12 +8,13 @@ pub struct OAuth2AuthorizationServiceImpl<
     Database,
     AuthorizationRepository,
     ClientService,
-    Hasher,
+    Hasher: ?Sized,
 > {
     database: Database,
     authorization_repo: Arc<AuthorizationRepository>,
     oauth2_client_service: Arc<ClientService>,
     hasher: Arc<Hasher>,
+    config: crate::config::OAuth2ServerConfig,
 }
 
 impl<Database, AuthorizationRepository, ClientService, Hasher>
@@ -27,7 +30,7 @@ where
             Handle<'h> = Database::Handle<'h>,
         >,
     ClientService: service::OAuth2ClientService,
-    Hasher: stardust::hash::Hasher,
+    Hasher: stardust::hash::LookupHasher + ?Sized,
 {
     pub fn new(
         database: Database,
@@ -40,16 +43,26 @@ where
             authorization_repo,
             oauth2_client_service,
             hasher,
+            config: Default::default(),
         }
     }
 
+    pub fn with_config(
+        mut self,
+        config: crate::config::OAuth2ServerConfig,
+    ) -> Self {
+        self.config = config;
+        self
+    }
+
     pub async fn issue_token(
         &self,
         command: &command::TokenCommand<'_>,
     ) -> stardust::Result<entity::OAuth2Token> {
         let Some(code) = command.code else {
             return Err(stardust::Error::InvalidParameter(
-                "Invalid code".into(),
+                ErrorDetail::new("oauth2.invalid_request", "Invalid code")
+                    .with_field("code", "required", "Missing code"),
             ));
         };
 
@@ -72,7 +85,10 @@ where
             )
             .await?
         else {
-            return Err(stardust::Error::NotFound(Cow::Owned(code.to_owned())));
+            return Err(stardust::Error::NotFound(ErrorDetail::new(
+                "oauth2.invalid_grant",
+                "Unknown code",
+            )));
         };
 
         if auth.auth_code_expires_at < chrono::Utc::now() {
@@ -81,14 +97,18 @@ where
 
         let access_token = stardust::utils::generate_uid();
         let refresh_token = stardust::utils::generate_uid();
-        let refresh_token_hash = self.hasher.hash(&refresh_token).await?;
-        auth.issue_token(access_token.clone(), refresh_token_hash);
+        let refresh_token_hash = self.hasher.digest(&refresh_token)?;
+        auth.issue_token(
+            access_token.clone(),
+            refresh_token_hash,
+            &self.config,
+        );
         self.authorization_repo
             .save_authorization(&mut self.database.handle(), &auth)
             .await?;
         let token = entity::OAuth2Token {
             access_token,
-            expires_in: 3600,
+            expires_in: self.config.access_token_ttl_secs.into(),
             refresh_token: Some(refresh_token),
             scope: auth.scope,
             token_type: "Bearer".into(),
@@ -102,7 +122,15 @@ where
     ) -> stardust::Result<entity::OAuth2Token> {
         let Some(refresh_token) = command.refresh_token else {
             return Err(stardust::Error::InvalidParameter(
-                "Invalid refresh_token".into(),
+                ErrorDetail::new(
+                    "oauth2.invalid_request",
+                    "Invalid refresh_token",
+                )
+                .with_field(
+                    "refresh_token",
+                    "required",
+                    "Missing refresh_token",
+                ),
             ));
         };
 
@@ -113,32 +141,40 @@ where
             })
             .await?;
 
-        let hash = self.hasher.hash(&refresh_token).await?;
-        let Some(mut auth) = self
-            .authorization_repo
-            .find_authorization(
-                &mut self.database.handle(),
-                &query::FindOAuth2AuthorizationQuery {
-                    auth_code_value: None,
-                    refresh_token_hash: Some(&hash),
-                    access_token: None,
-                },
-            )
-            .await?
-        else {
-            return Err(stardust::Error::NotFound(Cow::Owned(
-                refresh_token.to_owned(),
+        // tokens hashed with a rotated-out secret are still found
+        let mut found = None;
+        for hash in self.hasher.digests(refresh_token)? {
+            found = self
+                .authorization_repo
+                .find_authorization(
+                    &mut self.database.handle(),
+                    &query::FindOAuth2AuthorizationQuery {
+                        auth_code_value: None,
+                        refresh_token_hash: Some(&hash),
+                        access_token: None,
+                    },
+                )
+                .await?;
+            if found.is_some() {
+                break;
+            }
+        }
+        let Some(mut auth) = found else {
+            return Err(stardust::Error::NotFound(ErrorDetail::new(
+                "oauth2.invalid_grant",
+                "Unknown refresh_token",
             )));
         };
 
         let access_token = stardust::utils::generate_uid();
-        auth.refresh_token(access_token.clone());
+        auth.refresh_token(access_token.clone(), &self.config);
+        auth.refresh_token_hash = self.hasher.digest(refresh_token)?;
         self.authorization_repo
             .save_authorization(&mut self.database.handle(), &auth)
             .await?;
         let token = entity::OAuth2Token {
             access_token,
-            expires_in: 3600,
+            expires_in: self.config.access_token_ttl_secs.into(),
             refresh_token: Some(refresh_token.to_owned()),
             scope: auth.scope,
             token_type: "Bearer".into(),
@@ -162,7 +198,7 @@ where
             Handle<'h> = Database::Handle<'h>,
         >,
     ClientService: service::OAuth2ClientService,
-    Hasher: stardust::hash::Hasher,
+    Hasher: stardust::hash::LookupHasher + ?Sized,
 {
     async fn verify(
         &self,
@@ -176,7 +212,8 @@ where
             .await?;
 
         if clients.len() == 0 {
-            return Err(stardust::Error::NotFound(Cow::Owned(
+            return Err(stardust::Error::NotFound(ErrorDetail::new(
+                "oauth2.unknown_client",
                 command.client_id.to_owned(),
             )));
         }
@@ -188,13 +225,26 @@ where
             &command.redirect_uri,
         ) {
             return Err(stardust::Error::InvalidParameter(
-                "Invalid Redirect uri".into(),
+                ErrorDetail::new(
+                    "oauth2.invalid_redirect_uri",
+                    "Invalid Redirect uri",
+                )
+                .with_field(
+                    "redirect_uri",
+                    "not_registered",
+                    "Not registered for the client",
+                ),
             ));
         }
 
         if !stardust::utils::contains(&client.scopes, &command.scope) {
             return Err(stardust::Error::InvalidParameter(
-                "Invalid scope".into(),
+                ErrorDetail::new("oauth2.invalid_scope", "Invalid scope")
+                    .with_field(
+                        "scope",
+                        "not_allowed",
+                        "Not allowed for the client",
+                    ),
             ));
         }
 
@@ -212,6 +262,8 @@ where
             command.verify_command.scope.to_owned(),
             command.verify_command.state.to_owned(),
         );
+        authorization.auth_code_expires_at =
+            authorization.auth_code_issued_at + self.config.auth_code_ttl();
         if let Some(config) = &command.config {
             authorization.config = config.clone();
         }
@@ -230,7 +282,15 @@ where
             "authorization_code" => self.issue_token(&command).await,
             "refresh_token" => self.refresh_token(&command).await,
             _ => Err(stardust::Error::InvalidParameter(
-                "Invalid grant_type".into(),
+                ErrorDetail::new(
+                    "oauth2.unsupported_grant_type",
+                    "Invalid grant_type",
+                )
+                .with_field(
+                    "grant_type",
+                    "unsupported",
+                    "Unsupported grant_type",
+                ),
             )),
         }
     }
@@ -240,7 +300,7 @@ where
         query: &query::FindOAuth2UserQuery<'_>,
     ) -> stardust::Result<Option<entity::OAuthUserAggregate>> {
         self.authorization_repo
-            .find_user(&mut self.database.handle(), &query)
+            .find_user(&mut self.database.read_handle(), &query)
             .await
     }
 
@@ -1,14 +1,16 @@
-use std::{borrow::Cow, sync::Arc};
+use std::sync::Arc;
+
+use stardust::ErrorDetail;
 
 use crate::{command, entity, query, service::OAuth2ClientService};
 
-pub struct OAuth2ClientServiceImpl<Database, ClientRepository, Hasher> {
+pub struct OAuth2ClientServiceImpl<Database, ClientRepository, Hasher: ?Sized> {
     database: Database,
     client_repo: Arc<ClientRepository>,
     hasher: Arc<Hasher>,
 }
 
-impl<Database, ClientRepository, Hasher>
+impl<Database, ClientRepository, Hasher: ?Sized>
     OAuth2ClientServiceImpl<Database, ClientRepository, Hasher>
 {
     pub fn new(
@@ -32,7 +34,7 @@ where
     ClientRepository: for<'h> crate::repository::ClientRepository<
             Handle<'h> = Database::Handle<'h>,
         >,
-    Hasher: stardust::hash::Hasher,
+    Hasher: stardust::hash::Hasher + ?Sized,
 {
     async fn create_client(
         &self,
@@ -84,7 +86,8 @@ where
             .await?;
 
         if clients.len() == 0 {
-            return Err(stardust::Error::NotFound(Cow::Owned(
+            return Err(stardust::Error::NotFound(ErrorDetail::new(
+                "oauth2.unknown_client",
                 command.client_id.to_owned(),
             )));
         }
@@ -95,10 +98,35 @@ where
             .verify(&command.client_secret, &client.client_secret_hash)
             .await?;
         if !result {
-            return Err(stardust::Error::InvalidParameter(
-                "Invalid client secret".into(),
-            ));
+            return Err(stardust::Error::InvalidParameter(ErrorDetail::new(
+                "oauth2.invalid_client",
+                "Invalid client secret",
+            )));
         }
         Ok(())
     }
+
+    async fn rotate_secret(
+        &self,
+        command: &command::RotateOAuth2ClientSecretCommand,
+    ) -> stardust::Result<entity::OAuth2ClientEntity> {
+        let Some(mut client) = self
+            .find_clients(&query::FindOAuth2ClientQuery {
+                client_id: Some(&command.client_id),
+            })
+            .await?
+            .into_iter()
+            .next()
+        else {
+            return Err(stardust::Error::NotFound(ErrorDetail::new(
+                "oauth2.unknown_client",
+                command.client_id.clone(),
+            )));
+        };
+        client.client_secret_hash =
+            self.hasher.hash(&command.client_secret).await?;
+        self.client_repo
+            .save_client_secret(&mut self.database.handle(), &client)
+            .await
+    }
 }
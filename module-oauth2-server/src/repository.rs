@@ -21,6 +21,12 @@ pub trait ClientRepository: Sync + Send {
         handle: &mut Self::Handle<'_>,
         command: &command::DeleteOAuth2ClientCommand,
     ) -> stardust::Result<()>;
+
+    async fn save_client_secret(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &entity::OAuth2ClientEntity,
+    ) -> stardust::Result<entity::OAuth2ClientEntity>;
 }
 
 #[async_trait::async_trait]
@@ -21,6 +21,11 @@ pub trait OAuth2ClientService: Sync + Send {
         &self,
         command: &command::VerifyOAuth2ClientCommand<'_>,
     ) -> stardust::Result<()>;
+
+    async fn rotate_secret(
+        &self,
+        command: &command::RotateOAuth2ClientSecretCommand,
+    ) -> stardust::Result<entity::OAuth2ClientEntity>;
 }
 
 #[async_trait::async_trait]
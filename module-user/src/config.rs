@@ -0,0 +1,95 @@
+//! `[modules.user]`, read with `Config::module::<UserConfig>(NAME)`.
+use stardust::FieldError;
+
+pub const NAME: &str = "user";
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct UserConfig {
+    pub apikey_prefix_len: usize, // leading characters kept to tell keys apart
+    pub admin: SeedAdminConfig,
+}
+
+/// Account created by the user migration, on the first run only.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SeedAdminConfig {
+    pub username: String,
+    pub email: String,
+    pub password: String, // or `password_file`
+}
+
+impl Default for UserConfig {
+    fn default() -> Self {
+        Self {
+            apikey_prefix_len: 8,
+            admin: SeedAdminConfig::default(),
+        }
+    }
+}
+
+impl Default for SeedAdminConfig {
+    fn default() -> Self {
+        Self {
+            username: "admin".into(),
+            email: "admin@stardust.io".into(),
+            password: "1qaz2wsx!".into(),
+        }
+    }
+}
+
+impl stardust::config::ModuleConfig for UserConfig {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        // api keys are `generate_uid`s, 32 hex characters
+        if !(1..=32).contains(&self.apikey_prefix_len) {
+            errors.push(FieldError::new(
+                "apikey_prefix_len",
+                "out_of_range",
+                "must be between 1 and 32",
+            ));
+        }
+        if self.admin.username.is_empty() {
+            errors.push(FieldError::new("admin.username", "required", "empty"));
+        }
+        if !self.admin.email.contains('@') {
+            errors.push(FieldError::new(
+                "admin.email",
+                "invalid_format",
+                "expected an email address",
+            ));
+        }
+        if self.admin.password.is_empty() {
+            errors.push(FieldError::new("admin.password", "required", "empty"));
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stardust::config::ModuleConfig;
+
+    #[test]
+    fn test_validate() {
+        let mut config = stardust::config::Config::test_config();
+        let user = config.module::<super::UserConfig>(super::NAME).unwrap();
+        assert!(user.validate().is_empty());
+
+        config.modules.insert(
+            super::NAME.into(),
+            serde_json::json!({
+                "apikey_prefix_len": 64,
+                "admin": { "email": "admin" },
+            }),
+        );
+        let error =
+            config.module::<super::UserConfig>(super::NAME).unwrap_err();
+        let fields: Vec<_> =
+            error.fields().iter().map(|f| f.field.as_ref()).collect();
+        assert_eq!(
+            fields,
+            ["modules.user.apikey_prefix_len", "modules.user.admin.email"]
+        );
+    }
+}
@@ -1,25 +1,25 @@
 use std::sync::Arc;
 
-use stardust::database::Database;
+use stardust::database::internal::postgres;
+use stardust::infra::migration::{Migration, Migrations, SqlMigration};
 
-use crate::service::UserService;
+use crate::entity;
 
-pub async fn migrate<C>(
-    database: stardust::infra::migration::Database,
-    container: Arc<C>,
-) -> stardust::Result<()>
+pub const NAME: &str = "user_migration";
+
+pub fn migrations<Hasher>(
+    hasher: Arc<Hasher>,
+    admin: crate::config::SeedAdminConfig,
+) -> Migrations<postgres::Database>
 where
-    C: crate::Container + 'static,
+    Hasher: stardust::hash::Hasher + ?Sized + 'static,
 {
-    const NAME: &str = "user_migration";
-    let mut handle = database.handle();
-    let mut migration =
-        stardust::infra::migration::get_latest(&mut handle, NAME)
-            .await?
-            .unwrap_or_default();
-    if migration.version == 0 {
-        sqlx::query(
-            r#"create table if not exists stardust_user (
+    Migrations::<postgres::Database>::new(NAME)
+        .register(SqlMigration {
+            version: 1,
+            description: "create user/apikey table",
+            statements: &[
+                r#"create table if not exists stardust_user (
                 id BIGSERIAL PRIMARY KEY,
                 username varchar(255) not null,
                 email varchar(255) not null,
@@ -28,13 +28,7 @@ where
                 created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
                 updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
             );"#,
-        )
-        .execute(handle.executor())
-        .await
-        .map_err(stardust::database::internal::into_error)?;
-
-        sqlx::query(
-            r#"create table if not exists stardust_user_account (
+                r#"create table if not exists stardust_user_account (
                 uid varchar(255) primary key,
                 user_id BIGINT not null,
                 account_type varchar(255) not null,
@@ -42,13 +36,7 @@ where
                 created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
                 updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
             );"#,
-        )
-        .execute(handle.executor())
-        .await
-        .map_err(stardust::database::internal::into_error)?;
-
-        sqlx::query(
-            r#"create table if not exists stardust_apikey (
+                r#"create table if not exists stardust_apikey (
                 id BIGSERIAL PRIMARY KEY,
                 user_id BIGINT not null,
                 key_hash varchar(255) not null,
@@ -59,44 +47,121 @@ where
                 last_used_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
                 deactivated_at TIMESTAMPTZ
             );"#,
+            ],
+            down: Some(&[
+                "DROP TABLE IF EXISTS stardust_apikey",
+                "DROP TABLE IF EXISTS stardust_user_account",
+                "DROP TABLE IF EXISTS stardust_user",
+            ]),
+        })
+        .register(
+            SeedAdminMigration::new(
+                Arc::new(super::user_repository::PostgresUserRepository::new()),
+                hasher,
+            )
+            .with_admin(admin),
         )
-        .execute(handle.executor())
-        .await
-        .map_err(stardust::database::internal::into_error)?;
+}
+
+pub async fn migrate<Hasher>(
+    database: postgres::Database,
+    hasher: Arc<Hasher>,
+) -> stardust::Result<()>
+where
+    Hasher: stardust::hash::Hasher + ?Sized + 'static,
+{
+    migrations(hasher, Default::default()).run(&database).await
+}
+
+/// Creates the initial admin account through the migration's transaction,
+/// so a failed seed leaves no half created user behind.
+pub struct SeedAdminMigration<UserRepository, Hasher: ?Sized> {
+    user_repo: Arc<UserRepository>,
+    hasher: Arc<Hasher>,
+    admin: crate::config::SeedAdminConfig,
+}
 
-        migration.name = NAME.into();
-        migration.version = 1;
-        migration.description = "create user/apikey table".into();
-        migration =
-            stardust::infra::migration::save(&mut handle, &migration).await?;
+impl<UserRepository, Hasher: ?Sized>
+    SeedAdminMigration<UserRepository, Hasher>
+{
+    pub fn new(user_repo: Arc<UserRepository>, hasher: Arc<Hasher>) -> Self {
+        Self {
+            user_repo,
+            hasher,
+            admin: Default::default(),
+        }
     }
 
-    if migration.version == 1 {
-        tracing::info!("migration 1 begin");
-        container
-            .user_service()
-            .signup(&crate::command::SignupCommand::Provisioned {
-                username: "admin".into(),
-                email: "admin@stardust.io".into(),
-                password: "1qaz2wsx!".into(),
-                account_type: crate::entity::AccountType::Local,
-                role: crate::entity::Role::Admin,
-                status: crate::entity::Status::Active,
-            })
-            .await?;
-        migration.name = NAME.into();
-        migration.version = 2;
-        migration.description = "add admin user".into();
-        migration = stardust::infra::migration::save(
-            &mut database.handle(),
-            &migration,
-        )
-        .await?;
+    pub fn with_admin(mut self, admin: crate::config::SeedAdminConfig) -> Self {
+        self.admin = admin;
+        self
     }
+}
 
-    if migration.version == 2 {
-        // No more migrations yet
+#[async_trait::async_trait]
+impl<Database, UserRepository, Hasher> Migration<Database>
+    for SeedAdminMigration<UserRepository, Hasher>
+where
+    Database: stardust::database::Database + 'static,
+    UserRepository: for<'h> crate::repository::UserRepository<
+            Handle<'h> = Database::Handle<'h>,
+        >,
+    Hasher: stardust::hash::Hasher + ?Sized,
+{
+    fn version(&self) -> i32 {
+        2
     }
 
-    Ok(())
+    fn description(&self) -> &str {
+        "add admin user"
+    }
+
+    fn checksum(&self) -> String {
+        // over the default account, so configuring another one once the
+        // seed ran isn't taken for an edit of the step
+        let admin = crate::config::SeedAdminConfig::default();
+        stardust::infra::migration::checksum(&[
+            &admin.username,
+            &admin.email,
+            &entity::Role::Admin.to_string(),
+            &entity::Status::Active.to_string(),
+        ])
+    }
+
+    async fn up(
+        &self,
+        handle: &mut Database::Handle<'_>,
+    ) -> stardust::Result<()> {
+        let now = chrono::Utc::now();
+        let user = self
+            .user_repo
+            .create_user(
+                handle,
+                &entity::UserEntity {
+                    id: 0,
+                    username: self.admin.username.clone(),
+                    email: self.admin.email.clone(),
+                    role: entity::Role::Admin,
+                    status: entity::Status::Active,
+                    created_at: now,
+                    updated_at: now,
+                },
+            )
+            .await?;
+        let password_hash = self.hasher.hash(&self.admin.password).await?;
+        self.user_repo
+            .create_user_account(
+                handle,
+                &entity::UserAccountEntity {
+                    uid: stardust::utils::generate_uid(),
+                    user_id: user.id,
+                    account_type: entity::AccountType::Local,
+                    password_hash,
+                    created_at: now,
+                    updated_at: now,
+                },
+            )
+            .await?;
+        Ok(())
+    }
 }
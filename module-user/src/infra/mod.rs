@@ -2,4 +2,6 @@ pub mod apikey_repository;
 pub mod migration;
 pub mod mock;
 pub mod model;
+pub mod mysql;
+pub mod sqlite;
 pub mod user_repository;
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use stardust::database::internal::mysql;
+use stardust::infra::migration::{Migrations, SqlMigration};
+
+// DDL commits implicitly on mysql, so the statements are written to be
+// rerun after a step fails halfway, see `stardust::infra::migration::mysql`
+pub fn migrations<Hasher>(
+    hasher: Arc<Hasher>,
+    admin: crate::config::SeedAdminConfig,
+) -> Migrations<mysql::Database>
+where
+    Hasher: stardust::hash::Hasher + ?Sized + 'static,
+{
+    Migrations::<mysql::Database>::new(crate::infra::migration::NAME)
+        .register(SqlMigration {
+            version: 1,
+            description: "create user/apikey table",
+            statements: &[
+                r#"create table if not exists stardust_user (
+                id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                username varchar(255) not null,
+                email varchar(255) not null,
+                role varchar(255) not null,
+                status varchar(255) not null,
+                created_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+                updated_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6)
+            );"#,
+                r#"create table if not exists stardust_user_account (
+                uid varchar(255) primary key,
+                user_id BIGINT not null,
+                account_type varchar(255) not null,
+                password_hash varchar(255) not null,
+                created_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+                updated_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6)
+            );"#,
+                r#"create table if not exists stardust_apikey (
+                id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                user_id BIGINT not null,
+                key_hash varchar(255) not null,
+                prefix varchar(255) not null,
+                description varchar(255) not null,
+                created_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+                updated_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+                last_used_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+                deactivated_at DATETIME(6)
+            );"#,
+            ],
+            down: Some(&[
+                "DROP TABLE IF EXISTS stardust_apikey",
+                "DROP TABLE IF EXISTS stardust_user_account",
+                "DROP TABLE IF EXISTS stardust_user",
+            ]),
+        })
+        .register(
+            crate::infra::migration::SeedAdminMigration::new(
+                Arc::new(super::user_repository::MySqlUserRepository::new()),
+                hasher,
+            )
+            .with_admin(admin),
+        )
+}
+
+pub async fn migrate<Hasher>(
+    database: mysql::Database,
+    hasher: Arc<Hasher>,
+) -> stardust::Result<()>
+where
+    Hasher: stardust::hash::Hasher + ?Sized + 'static,
+{
+    migrations(hasher, Default::default()).run(&database).await
+}
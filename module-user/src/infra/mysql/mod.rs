@@ -0,0 +1,3 @@
+pub mod apikey_repository;
+pub mod migration;
+pub mod user_repository;
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use stardust::database::internal::sqlite;
+use stardust::infra::migration::{Migrations, SqlMigration};
+
+pub fn migrations<Hasher>(
+    hasher: Arc<Hasher>,
+    admin: crate::config::SeedAdminConfig,
+) -> Migrations<sqlite::Database>
+where
+    Hasher: stardust::hash::Hasher + ?Sized + 'static,
+{
+    Migrations::<sqlite::Database>::new(crate::infra::migration::NAME)
+        .register(SqlMigration {
+            version: 1,
+            description: "create user/apikey table",
+            statements: &[
+                r#"create table if not exists stardust_user (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                username TEXT not null,
+                email TEXT not null,
+                role TEXT not null,
+                status TEXT not null,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );"#,
+                r#"create table if not exists stardust_user_account (
+                uid TEXT primary key,
+                user_id INTEGER not null,
+                account_type TEXT not null,
+                password_hash TEXT not null,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );"#,
+                r#"create table if not exists stardust_apikey (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER not null,
+                key_hash TEXT not null,
+                prefix TEXT not null,
+                description TEXT not null,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                last_used_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                deactivated_at DATETIME
+            );"#,
+            ],
+            down: Some(&[
+                "DROP TABLE IF EXISTS stardust_apikey",
+                "DROP TABLE IF EXISTS stardust_user_account",
+                "DROP TABLE IF EXISTS stardust_user",
+            ]),
+        })
+        .register(
+            crate::infra::migration::SeedAdminMigration::new(
+                Arc::new(super::user_repository::SqliteUserRepository::new()),
+                hasher,
+            )
+            .with_admin(admin),
+        )
+}
+
+pub async fn migrate<Hasher>(
+    database: sqlite::Database,
+    hasher: Arc<Hasher>,
+) -> stardust::Result<()>
+where
+    Hasher: stardust::hash::Hasher + ?Sized + 'static,
+{
+    migrations(hasher, Default::default()).run(&database).await
+}
@@ -0,0 +1,144 @@
+pub mod apikey_repository;
+pub mod migration;
+pub mod user_repository;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use stardust::database::internal::sqlite;
+
+    use crate::{
+        command, query,
+        service::{ApiKeyService, UserService},
+    };
+
+    type UserServiceImpl = crate::internal::UserServiceImpl<
+        sqlite::Database,
+        super::user_repository::SqliteUserRepository,
+        stardust::hash::NoOpHasher,
+    >;
+    type ApiKeyServiceImpl = crate::internal::ApiKeyServiceImpl<
+        sqlite::Database,
+        super::apikey_repository::SqliteApiKeyRepository,
+        crate::internal::ImmediateUsageTracker<
+            sqlite::Database,
+            super::apikey_repository::SqliteApiKeyRepository,
+        >,
+        stardust::hash::NoOpHasher,
+    >;
+
+    struct TestContainer {
+        user_service: Arc<UserServiceImpl>,
+        apikey_service: Arc<ApiKeyServiceImpl>,
+    }
+
+    impl crate::Container for TestContainer {
+        type UserService = UserServiceImpl;
+        type ApiKeyService = ApiKeyServiceImpl;
+
+        fn user_service(&self) -> Arc<Self::UserService> {
+            self.user_service.clone()
+        }
+        fn apikey_service(&self) -> Arc<Self::ApiKeyService> {
+            self.apikey_service.clone()
+        }
+    }
+
+    impl TestContainer {
+        fn new(database: sqlite::Database) -> Self {
+            let hasher = Arc::new(stardust::hash::NoOpHasher);
+            let apikey_repo = Arc::new(
+                super::apikey_repository::SqliteApiKeyRepository::new(),
+            );
+            Self {
+                user_service: Arc::new(UserServiceImpl::new(
+                    database.clone(),
+                    Arc::new(
+                        super::user_repository::SqliteUserRepository::new(),
+                    ),
+                    hasher.clone(),
+                )),
+                apikey_service: Arc::new(ApiKeyServiceImpl::new(
+                    database.clone(),
+                    apikey_repo.clone(),
+                    crate::internal::ImmediateUsageTracker::new(
+                        database,
+                        apikey_repo,
+                    ),
+                    hasher,
+                )),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_repositories() {
+        let (database, container) =
+            stardust::testing::ContainerBuilder::new(TestContainer::new)
+                .migrate(|database, _| {
+                    super::migration::migrate(
+                        database,
+                        Arc::new(stardust::hash::NoOpHasher),
+                    )
+                })
+                .build()
+                .await
+                .unwrap();
+        // migrations already applied are skipped
+        super::migration::migrate(
+            database,
+            Arc::new(stardust::hash::NoOpHasher),
+        )
+        .await
+        .unwrap();
+
+        let admin = container
+            .user_service
+            .login(&command::LoginCommand::Local {
+                email: "admin@stardust.io".into(),
+                password: "1qaz2wsx!".into(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(admin.user.username, "admin");
+        assert_eq!(admin.accounts.len(), 1);
+
+        let created = container
+            .apikey_service
+            .create_apikey(&command::CreateApiKeyCommand {
+                user_id: admin.user.id,
+                description: "test".into(),
+            })
+            .await
+            .unwrap();
+        let found = container
+            .apikey_service
+            .find_user(&query::FindApiKeyUserQuery {
+                key_hash: &created.secret,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.apikey_id, created.apikey.id);
+        assert_eq!(found.user.id, admin.user.id);
+
+        let deactivated = container
+            .apikey_service
+            .deactivate_apikey(&command::DeactivateApiKeyCommand {
+                request_user_id: admin.user.id,
+                apikey_id: created.apikey.id,
+            })
+            .await
+            .unwrap();
+        assert!(deactivated.deactivated_at.is_some());
+        let apikeys = container
+            .apikey_service
+            .find_apikeys(&query::FindApiKeysQuery {
+                user_id: admin.user.id,
+            })
+            .await
+            .unwrap();
+        assert_eq!(apikeys.len(), 1);
+    }
+}
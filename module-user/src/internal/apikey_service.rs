@@ -2,11 +2,17 @@ use std::sync::Arc;
 
 use crate::{command, entity, query, service::ApiKeyService};
 
-pub struct ApiKeyServiceImpl<Database, ApiKeyRepository, Tracker, Hasher> {
+pub struct ApiKeyServiceImpl<
+    Database,
+    ApiKeyRepository,
+    Tracker,
+    Hasher: ?Sized,
+> {
     database: Database,
     apikey_repo: Arc<ApiKeyRepository>,
     tracker: Arc<Tracker>,
     hasher: Arc<Hasher>,
+    config: crate::config::UserConfig,
 }
 
 impl<Database, ApiKeyRepository, Tracker, Hasher>
@@ -17,7 +23,7 @@ where
             Handle<'h> = Database::Handle<'h>,
         >,
     Tracker: crate::service::ApiKeyUsageTracker,
-    Hasher: stardust::hash::Hasher,
+    Hasher: stardust::hash::LookupHasher + ?Sized,
 {
     pub fn new(
         database: Database,
@@ -30,8 +36,14 @@ where
             apikey_repo,
             tracker,
             hasher,
+            config: Default::default(),
         }
     }
+
+    pub fn with_config(mut self, config: crate::config::UserConfig) -> Self {
+        self.config = config;
+        self
+    }
 }
 
 impl<Database, ApiKeyRepository, Tracker, Hasher> ApiKeyService
@@ -42,20 +54,20 @@ where
             Handle<'h> = Database::Handle<'h>,
         >,
     Tracker: crate::service::ApiKeyUsageTracker,
-    Hasher: stardust::hash::Hasher,
+    Hasher: stardust::hash::LookupHasher + ?Sized,
 {
     async fn create_apikey(
         &self,
         command: &command::CreateApiKeyCommand,
     ) -> stardust::Result<entity::ApiKeyWithSecret> {
         let key = stardust::utils::generate_uid();
-        let key_hash = self.hasher.hash(&key).await?;
+        let key_hash = self.hasher.digest(&key)?;
         let now = chrono::Utc::now();
         let entity = entity::ApiKeyEntity {
             id: 0,
             user_id: command.user_id,
             key_hash: key_hash,
-            prefix: key[..8].to_string(),
+            prefix: key[..self.config.apikey_prefix_len].to_string(),
             description: command.description.clone(),
             created_at: now,
             updated_at: now,
@@ -77,23 +89,26 @@ where
         &self,
         query: &query::FindApiKeyUserQuery<'_>,
     ) -> stardust::Result<Option<entity::ApiKeyUserAggregate>> {
-        let key_hash = self.hasher.hash(&query.key_hash).await?;
-        let result = self
-            .apikey_repo
-            .find_user(
-                &mut self.database.handle(),
-                &query::FindApiKeyUserQuery {
-                    key_hash: &key_hash,
-                },
-            )
-            .await;
-        match result {
-            Ok(Some(ref user)) => {
-                self.tracker.track_usage(user.apikey_id).await?;
+        // keys hashed with a rotated-out secret are still found
+        let mut result = None;
+        for key_hash in self.hasher.digests(query.key_hash)? {
+            result = self
+                .apikey_repo
+                .find_user(
+                    &mut self.database.read_handle(),
+                    &query::FindApiKeyUserQuery {
+                        key_hash: &key_hash,
+                    },
+                )
+                .await?;
+            if result.is_some() {
+                break;
             }
-            _ => {}
         }
-        result
+        if let Some(ref user) = result {
+            self.tracker.track_usage(user.apikey_id).await?;
+        }
+        Ok(result)
     }
 
     async fn find_apikeys(
@@ -102,7 +117,7 @@ where
     ) -> stardust::Result<Vec<entity::ApiKeyEntity>> {
         return self
             .apikey_repo
-            .find_apikeys(&mut self.database.handle(), &query)
+            .find_apikeys(&mut self.database.read_handle(), &query)
             .await;
     }
 
@@ -115,7 +130,10 @@ where
             .get_apikey(&mut self.database.handle(), command.apikey_id)
             .await?;
         if result.is_none() {
-            return Err(stardust::Error::NotFound("".into()));
+            return Err(stardust::Error::NotFound(stardust::ErrorDetail::new(
+                "user.apikey_not_found",
+                "apikey",
+            )));
         }
         let mut key = result.unwrap();
         if key.user_id != command.request_user_id {
@@ -1,28 +1,49 @@
 use std::sync::Arc;
 
-use stardust::{database::Database, database::internal::postgres};
+use crate::service::ApiKeyUsageTracker;
 
-use crate::{infra::apikey_repository, service::ApiKeyUsageTracker};
-
-pub struct ImmediateUsageTracker {
-    database: postgres::Database,
+pub struct ImmediateUsageTracker<Database, ApiKeyRepository> {
+    database: Database,
+    apikey_repo: Arc<ApiKeyRepository>,
 }
 
-impl ImmediateUsageTracker {
-    pub fn new(database: postgres::Database) -> Arc<Self> {
-        Arc::new(Self { database })
+impl<Database, ApiKeyRepository>
+    ImmediateUsageTracker<Database, ApiKeyRepository>
+where
+    Database: stardust::database::Database,
+    ApiKeyRepository: for<'h> crate::repository::ApiKeyRepository<
+            Handle<'h> = Database::Handle<'h>,
+        >,
+{
+    pub fn new(
+        database: Database,
+        apikey_repo: Arc<ApiKeyRepository>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            database,
+            apikey_repo,
+        })
     }
 }
 
 #[async_trait::async_trait]
-impl ApiKeyUsageTracker for ImmediateUsageTracker {
+impl<Database, ApiKeyRepository> ApiKeyUsageTracker
+    for ImmediateUsageTracker<Database, ApiKeyRepository>
+where
+    Database: stardust::database::Database,
+    ApiKeyRepository: for<'h> crate::repository::ApiKeyRepository<
+            Handle<'h> = Database::Handle<'h>,
+        >,
+{
     async fn track_usage(&self, apikey_id: i64) -> stardust::Result<()> {
-        if let Err(e) = apikey_repository::update_last_used_at(
-            &mut self.database.handle(),
-            apikey_id,
-            chrono::Utc::now(),
-        )
-        .await
+        if let Err(e) = self
+            .apikey_repo
+            .update_last_used_at(
+                &mut self.database.handle(),
+                apikey_id,
+                chrono::Utc::now(),
+            )
+            .await
         {
             tracing::warn!(
                 "Failed to update last_used_at for apikey {}: {}",
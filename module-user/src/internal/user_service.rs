@@ -5,9 +5,7 @@ use crate::{
     entity, query,
 };
 
-use stardust::database::Handle;
-
-pub struct UserServiceImpl<Database, UserRepository, Hasher> {
+pub struct UserServiceImpl<Database, UserRepository, Hasher: ?Sized> {
     database: Database,
     user_repo: Arc<UserRepository>,
     hasher: Arc<Hasher>,
@@ -20,7 +18,7 @@ where
     UserRepository: for<'h> crate::repository::UserRepository<
             Handle<'h> = Database::Handle<'h>,
         >,
-    Hasher: stardust::hash::Hasher,
+    Hasher: stardust::hash::Hasher + ?Sized,
 {
     pub fn new(
         database: Database,
@@ -43,6 +41,7 @@ where
             Ok(hash) => {
                 let mut save_user_account = user_accout.clone();
                 save_user_account.password_hash = hash;
+                save_user_account.updated_at = chrono::Utc::now();
                 if let Err(e) = self
                     .user_repo
                     .save_user_account(
@@ -69,7 +68,7 @@ where
     UserRepository: for<'h> crate::repository::UserRepository<
             Handle<'h> = Database::Handle<'h>,
         >,
-    Hasher: stardust::hash::Hasher,
+    Hasher: stardust::hash::Hasher + ?Sized,
 {
     async fn signup(
         &self,
@@ -83,9 +82,13 @@ where
             )
             .await?
         {
-            return Err(stardust::Error::AlreadyExists(user.email.into()));
+            return Err(stardust::Error::AlreadyExists(
+                stardust::ErrorDetail::new("user.email_taken", user.email)
+                    .with_field("email", "taken", "Already registered"),
+            ));
         }
-        let mut handle = self.database.tx_handle().await?;
+        // hashed up front, so a retried transaction doesn't hash again
+        let password_hash = self.hasher.hash(command.password()).await?;
         let now = chrono::Utc::now();
         let user_entity = entity::UserEntity {
             id: 0,
@@ -96,23 +99,33 @@ where
             created_at: now,
             updated_at: now,
         };
-        let user_entity =
-            self.user_repo.create_user(&mut handle, &user_entity).await?;
-
-        let password_hash = self.hasher.hash(command.password()).await?;
-        let user_account_entity = entity::UserAccountEntity {
-            uid: stardust::utils::generate_uid(),
-            user_id: user_entity.id,
-            account_type: command.account_type(),
-            password_hash,
-            created_at: now,
-            updated_at: now,
-        };
-        let _ = self
-            .user_repo
-            .create_user_account(&mut handle, &user_account_entity)
+        let user_entity = &user_entity;
+        let password_hash = &password_hash;
+        let account_type = command.account_type();
+        let account_type = &account_type;
+        let (user_entity, user_account_entity) = self
+            .database
+            .transaction(|mut handle| {
+                Box::pin(async move {
+                    let user_entity = self
+                        .user_repo
+                        .create_user(&mut handle, user_entity)
+                        .await?;
+                    let user_account_entity = entity::UserAccountEntity {
+                        uid: stardust::utils::generate_uid(),
+                        user_id: user_entity.id,
+                        account_type: account_type.clone(),
+                        password_hash: password_hash.clone(),
+                        created_at: now,
+                        updated_at: now,
+                    };
+                    self.user_repo
+                        .create_user_account(&mut handle, &user_account_entity)
+                        .await?;
+                    Ok((user_entity, user_account_entity))
+                })
+            })
             .await?;
-        handle.commit().await?;
         Ok(entity::UserAggregate {
             user: user_entity,
             accounts: vec![user_account_entity],
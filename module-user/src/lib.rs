@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 pub mod command;
+pub mod config;
 pub mod entity;
 pub mod infra;
 pub mod interface;
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::routing::get;
+use module_user::interface::extract::AdminUser;
+use stardust::logging::FilterStatus;
+
+use crate::container::Container;
+
+const MAX_REVERT_AFTER_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Debug, serde::Deserialize)]
+struct SetFilterRequest {
+    filter: String,
+    /// Goes back to the current filter after this many seconds.
+    revert_after_secs: Option<u64>,
+}
+
+async fn get_filter(
+    AdminUser(_, _): AdminUser<stardust::Error>,
+) -> stardust::Result<axum::Json<FilterStatus>> {
+    Ok(axum::Json(stardust::logging::filter_status()?))
+}
+
+async fn set_filter(
+    AdminUser(user, _): AdminUser<stardust::Error>,
+    axum::Json(req): axum::Json<SetFilterRequest>,
+) -> stardust::Result<axum::Json<FilterStatus>> {
+    let status = match req.revert_after_secs {
+        Some(secs) if secs == 0 || secs > MAX_REVERT_AFTER_SECS => {
+            return Err(stardust::Error::InvalidParameter(
+                stardust::ErrorDetail::new(
+                    "logging.invalid_revert_after",
+                    "revert_after_secs must be between 1 and 7 days",
+                )
+                .with_field(
+                    "revert_after_secs",
+                    "out_of_range",
+                    format!("must be 1 to {}", MAX_REVERT_AFTER_SECS),
+                ),
+            ));
+        }
+        Some(secs) => stardust::logging::set_filter_for(
+            &req.filter,
+            Duration::from_secs(secs),
+        )?,
+        None => {
+            stardust::logging::set_filter(&req.filter)?;
+            stardust::logging::filter_status()?
+        }
+    };
+    tracing::warn!(
+        "logging: filter set to {} by {}{}",
+        req.filter,
+        user.username,
+        match status.revert_at {
+            Some(at) => format!(", reverting at {}", at),
+            None => String::new(),
+        }
+    );
+    Ok(axum::Json(status))
+}
+
+pub fn routes(container: Arc<Container>) -> axum::Router {
+    axum::Router::new()
+        .route("/admin/logging/filter", get(get_filter).put(set_filter))
+        .with_state(container)
+}
@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use module_oauth2_server::service::OAuth2ClientService;
+use module_user::service::UserService;
+
+use crate::container::Container;
+
+const CONFIG_POLL_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(2);
+
+#[derive(Debug, clap::Parser)]
+#[command(name = "stardust-app", version)]
+pub struct Cli {
+    /// Config file, testenv/config.test.toml when omitted
+    #[arg(long, global = true)]
+    pub config: Option<String>,
+
+    /// Overlays <config stem>.<profile>.<ext> on top of --config
+    #[arg(long, global = true, requires = "config")]
+    pub profile: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Run the http server, the default without a command
+    Serve {
+        /// Apply pending migrations before serving
+        #[arg(long)]
+        migrate: bool,
+    },
+    /// Apply, revert or inspect migrations
+    Migrate {
+        #[command(subcommand)]
+        command: MigrateCommand,
+    },
+    /// Create an active admin account
+    CreateAdmin {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: String,
+        /// Generated and printed when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Register an oauth2 client, printing its secret once
+    CreateOauthClient {
+        #[arg(long)]
+        name: String,
+        /// Generated when omitted
+        #[arg(long)]
+        client_id: Option<String>,
+        #[arg(long = "redirect-uri", required = true)]
+        redirect_uris: Vec<String>,
+        #[arg(long = "scope", default_value = "read")]
+        scopes: Vec<String>,
+        #[arg(
+            long = "grant-type",
+            default_values = ["authorization_code", "refresh_token"]
+        )]
+        grant_types: Vec<String>,
+        #[arg(long = "auth-method", default_value = "client_secret_post")]
+        auth_methods: Vec<String>,
+    },
+    /// Replace an oauth2 client's secret, printing the new one once
+    RotateClientSecret {
+        #[arg(long)]
+        client_id: String,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum MigrateCommand {
+    /// Apply every pending migration
+    Up,
+    /// Roll a module back to a version, in reverse order
+    Down {
+        /// user_migration | oauth2_server_migration
+        #[arg(long)]
+        module: String,
+        #[arg(long)]
+        to: i32,
+    },
+    /// List applied and pending migrations
+    Status,
+}
+
+impl Cli {
+    pub fn load_config(&self) -> stardust::Result<stardust::config::Config> {
+        match &self.config {
+            Some(path) => stardust::config::Config::load_for(
+                path,
+                self.profile.as_deref(),
+                crate::container::DATABASE_SCHEMES,
+            ),
+            None => {
+                let config = stardust::config::Config::test_config();
+                config.validate_for(crate::container::DATABASE_SCHEMES)?;
+                Ok(config)
+            }
+        }
+    }
+
+    /// Watches the files `config` came from, for `Serve`.
+    pub fn config_watcher(
+        &self,
+        config: stardust::config::Config,
+    ) -> stardust::config::ConfigWatcher {
+        let path = self
+            .config
+            .clone()
+            .unwrap_or_else(stardust::config::Config::test_config_path);
+        stardust::config::ConfigWatcher::new(
+            &path,
+            self.profile.as_deref(),
+            config,
+        )
+    }
+}
+
+pub async fn run(
+    cli: &Cli,
+    command: Command,
+    config: stardust::config::Config,
+) -> stardust::Result<()> {
+    let container = Container::build(config.clone()).await?;
+    match command {
+        Command::Serve { migrate } => {
+            if migrate {
+                container.migrator().run().await?;
+            }
+            let watcher = cli.config_watcher(config.clone());
+            let live = watcher.subscribe();
+            stardust::logging::follow(live.clone());
+            Arc::new(watcher).spawn(CONFIG_POLL_INTERVAL);
+            crate::serve(&config, container, live).await
+        }
+        Command::Migrate { command } => migrate(&container, command).await,
+        Command::CreateAdmin {
+            username,
+            email,
+            password,
+        } => create_admin(&container, username, email, password).await,
+        Command::CreateOauthClient {
+            name,
+            client_id,
+            redirect_uris,
+            scopes,
+            grant_types,
+            auth_methods,
+        } => {
+            let client_secret = stardust::utils::generate_uid();
+            let client = container
+                .oauth2_server_module
+                .oauth2_client_service
+                .create_client(
+                    &module_oauth2_server::command::CreateOAuth2ClientCommand {
+                        name,
+                        client_id: client_id
+                            .unwrap_or_else(stardust::utils::generate_uid),
+                        client_secret: client_secret.clone(),
+                        redirect_uris,
+                        grant_types,
+                        auth_methods,
+                        scopes,
+                    },
+                )
+                .await?;
+            println!("client_id: {}", client.client_id);
+            println!("client_secret: {}", client_secret);
+            Ok(())
+        }
+        Command::RotateClientSecret { client_id } => {
+            let client_secret = stardust::utils::generate_uid();
+            let client = container
+                .oauth2_server_module
+                .oauth2_client_service
+                .rotate_secret(
+                    &module_oauth2_server::command::RotateOAuth2ClientSecretCommand {
+                        client_id,
+                        client_secret: client_secret.clone(),
+                    },
+                )
+                .await?;
+            println!("client_id: {}", client.client_id);
+            println!("client_secret: {}", client_secret);
+            Ok(())
+        }
+    }
+}
+
+async fn migrate(
+    container: &Arc<Container>,
+    command: MigrateCommand,
+) -> stardust::Result<()> {
+    let migrator = container.migrator();
+    match command {
+        MigrateCommand::Up => migrator.run().await,
+        MigrateCommand::Down { module, to } => {
+            migrator.rollback(&module, to).await
+        }
+        MigrateCommand::Status => {
+            for status in migrator.status().await? {
+                println!(
+                    "{:<24} v{:<4} {:<8} {:<25} {}",
+                    status.name,
+                    status.version,
+                    format!("{:?}", status.state),
+                    status
+                        .applied_at
+                        .map(|at| at.to_rfc3339())
+                        .unwrap_or_default(),
+                    status.description,
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn create_admin(
+    container: &Arc<Container>,
+    username: String,
+    email: String,
+    password: Option<String>,
+) -> stardust::Result<()> {
+    let generated = password.is_none();
+    let password = password.unwrap_or_else(stardust::utils::generate_uid);
+    let admin = container
+        .user_module
+        .user_service
+        .signup(&module_user::command::SignupCommand::Provisioned {
+            username,
+            email,
+            password: password.clone(),
+            account_type: module_user::entity::AccountType::Local,
+            role: module_user::entity::Role::Admin,
+            status: module_user::entity::Status::Active,
+        })
+        .await?;
+    println!("admin: {} <{}>", admin.user.username, admin.user.email);
+    if generated {
+        println!("password: {}", password);
+    }
+    Ok(())
+}
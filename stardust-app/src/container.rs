@@ -1,7 +1,11 @@
+#[cfg(all(feature = "sqlite", feature = "mysql"))]
+compile_error!("features `sqlite` and `mysql` are mutually exclusive");
+
+#[cfg(not(any(feature = "sqlite", feature = "mysql")))]
 mod env_dev {
 
-    pub type Hasher = stardust::hash::NoOpHasher;
-    pub type PasswordHasher = stardust::hash::NoOpHasher;
+    pub type PasswordHasher = dyn stardust::hash::Hasher;
+    pub type SecretHasher = dyn stardust::hash::LookupHasher;
     pub type Database = stardust::database::internal::postgres::Database;
 
     pub type UserRepository =
@@ -12,14 +16,17 @@ mod env_dev {
         PasswordHasher,
     >;
 
-    pub type ApiKeyUsageTracker = module_user::internal::ImmediateUsageTracker;
+    pub type ApiKeyUsageTracker = module_user::internal::ImmediateUsageTracker<
+        Database,
+        ApiKeyRepository,
+    >;
     pub type ApiKeyRepository =
         module_user::infra::apikey_repository::PostgresApiKeyRepository;
     pub type ApiKeyService = module_user::internal::ApiKeyServiceImpl<
         Database,
         ApiKeyRepository,
         ApiKeyUsageTracker,
-        Hasher,
+        SecretHasher,
     >;
 
     pub type OAuth2ClientRepository =
@@ -28,7 +35,7 @@ mod env_dev {
         module_oauth2_server::internal::OAuth2ClientServiceImpl<
             Database,
             OAuth2ClientRepository,
-            Hasher,
+            PasswordHasher,
         >;
 
     pub type OAuth2AuthorizationRepository =
@@ -38,23 +45,162 @@ mod env_dev {
             Database,
             OAuth2AuthorizationRepository,
             OAuth2ClientService,
-            Hasher,
+            SecretHasher,
+        >;
+
+    pub fn migrator(
+        container: &super::Container,
+    ) -> stardust::infra::migration::Migrator<Database> {
+        stardust::infra::migration::Migrator::new(container.database.clone())
+            .module(module_user::infra::migration::migrations(
+                container.password_hasher.clone(),
+                container.user_module.config.admin.clone(),
+            ))
+            .module(module_oauth2_server::infra::migration::migrations())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod env_sqlite {
+
+    pub type PasswordHasher = dyn stardust::hash::Hasher;
+    pub type SecretHasher = dyn stardust::hash::LookupHasher;
+    pub type Database = stardust::database::internal::sqlite::Database;
+
+    pub type UserRepository =
+        module_user::infra::sqlite::user_repository::SqliteUserRepository;
+    pub type UserService = module_user::internal::UserServiceImpl<
+        Database,
+        UserRepository,
+        PasswordHasher,
+    >;
+
+    pub type ApiKeyUsageTracker = module_user::internal::ImmediateUsageTracker<
+        Database,
+        ApiKeyRepository,
+    >;
+    pub type ApiKeyRepository =
+        module_user::infra::sqlite::apikey_repository::SqliteApiKeyRepository;
+    pub type ApiKeyService = module_user::internal::ApiKeyServiceImpl<
+        Database,
+        ApiKeyRepository,
+        ApiKeyUsageTracker,
+        SecretHasher,
+    >;
+
+    pub type OAuth2ClientRepository =
+        module_oauth2_server::infra::sqlite::client_repository::SqliteClientRepository;
+    pub type OAuth2ClientService =
+        module_oauth2_server::internal::OAuth2ClientServiceImpl<
+            Database,
+            OAuth2ClientRepository,
+            PasswordHasher,
+        >;
+
+    pub type OAuth2AuthorizationRepository =
+        module_oauth2_server::infra::sqlite::authorization_repository::SqliteAuthorizationRepository;
+    pub type OAuth2AuthorizationService =
+        module_oauth2_server::internal::OAuth2AuthorizationServiceImpl<
+            Database,
+            OAuth2AuthorizationRepository,
+            OAuth2ClientService,
+            SecretHasher,
         >;
+
+    pub fn migrator(
+        container: &super::Container,
+    ) -> stardust::infra::migration::Migrator<Database> {
+        stardust::infra::migration::Migrator::new(container.database.clone())
+            .module(module_user::infra::sqlite::migration::migrations(
+                container.password_hasher.clone(),
+                container.user_module.config.admin.clone(),
+            ))
+            .module(module_oauth2_server::infra::sqlite::migration::migrations())
+    }
 }
 
+#[cfg(feature = "mysql")]
+mod env_mysql {
+
+    pub type PasswordHasher = dyn stardust::hash::Hasher;
+    pub type SecretHasher = dyn stardust::hash::LookupHasher;
+    pub type Database = stardust::database::internal::mysql::Database;
+
+    pub type UserRepository =
+        module_user::infra::mysql::user_repository::MySqlUserRepository;
+    pub type UserService = module_user::internal::UserServiceImpl<
+        Database,
+        UserRepository,
+        PasswordHasher,
+    >;
+
+    pub type ApiKeyUsageTracker = module_user::internal::ImmediateUsageTracker<
+        Database,
+        ApiKeyRepository,
+    >;
+    pub type ApiKeyRepository =
+        module_user::infra::mysql::apikey_repository::MySqlApiKeyRepository;
+    pub type ApiKeyService = module_user::internal::ApiKeyServiceImpl<
+        Database,
+        ApiKeyRepository,
+        ApiKeyUsageTracker,
+        SecretHasher,
+    >;
+
+    pub type OAuth2ClientRepository =
+        module_oauth2_server::infra::mysql::client_repository::MySqlClientRepository;
+    pub type OAuth2ClientService =
+        module_oauth2_server::internal::OAuth2ClientServiceImpl<
+            Database,
+            OAuth2ClientRepository,
+            PasswordHasher,
+        >;
+
+    pub type OAuth2AuthorizationRepository =
+        module_oauth2_server::infra::mysql::authorization_repository::MySqlAuthorizationRepository;
+    pub type OAuth2AuthorizationService =
+        module_oauth2_server::internal::OAuth2AuthorizationServiceImpl<
+            Database,
+            OAuth2AuthorizationRepository,
+            OAuth2ClientService,
+            SecretHasher,
+        >;
+
+    pub fn migrator(
+        container: &super::Container,
+    ) -> stardust::infra::migration::Migrator<Database> {
+        stardust::infra::migration::Migrator::new(container.database.clone())
+            .module(module_user::infra::mysql::migration::migrations(
+                container.password_hasher.clone(),
+                container.user_module.config.admin.clone(),
+            ))
+            .module(module_oauth2_server::infra::mysql::migration::migrations())
+    }
+}
+
+#[cfg(not(any(feature = "sqlite", feature = "mysql")))]
 use env_dev::*;
+#[cfg(feature = "mysql")]
+use env_mysql::*;
+#[cfg(feature = "sqlite")]
+use env_sqlite::*;
 use std::sync::Arc;
 
+/// `database.url` schemes of the backend the binary is built with.
+pub const DATABASE_SCHEMES: &[&str] = Database::SCHEMES;
+
 pub struct UserModule {
+    pub config: module_user::config::UserConfig,
     pub user_service: Arc<UserService>,
     pub apikey_service: Arc<ApiKeyService>,
 }
 
 impl UserModule {
     pub fn new(
+        config: module_user::config::UserConfig,
         database: Database,
         password_hasher: Arc<PasswordHasher>,
-        hasher: Arc<Hasher>,
+        secret_hasher: Arc<SecretHasher>,
     ) -> Self {
         let user_repo = Arc::new(UserRepository::new());
         let user_service = Arc::new(UserService::new(
@@ -64,14 +210,19 @@ impl UserModule {
         ));
 
         let apikey_repo = Arc::new(ApiKeyRepository::new());
-        let apikey_usage_tracker = ApiKeyUsageTracker::new(database.clone());
-        let apikey_service = Arc::new(ApiKeyService::new(
-            database.clone(),
-            apikey_repo.clone(),
-            apikey_usage_tracker.clone(),
-            hasher.clone(),
-        ));
+        let apikey_usage_tracker =
+            ApiKeyUsageTracker::new(database.clone(), apikey_repo.clone());
+        let apikey_service = Arc::new(
+            ApiKeyService::new(
+                database.clone(),
+                apikey_repo.clone(),
+                apikey_usage_tracker.clone(),
+                secret_hasher.clone(),
+            )
+            .with_config(config.clone()),
+        );
         Self {
+            config,
             user_service,
             apikey_service,
         }
@@ -84,23 +235,30 @@ pub struct OAuth2ServerModule {
 }
 
 impl OAuth2ServerModule {
-    pub fn new(database: Database, hasher: Arc<Hasher>) -> Self {
+    pub fn new(
+        config: module_oauth2_server::config::OAuth2ServerConfig,
+        database: Database,
+        password_hasher: Arc<PasswordHasher>,
+        secret_hasher: Arc<SecretHasher>,
+    ) -> Self {
         let oauth2_client_repo = Arc::new(OAuth2ClientRepository::new());
         let oauth2_client_service = Arc::new(OAuth2ClientService::new(
             database.clone(),
             oauth2_client_repo.clone(),
-            hasher.clone(),
+            password_hasher.clone(),
         ));
 
         let oauth2_authorization_repo =
             Arc::new(OAuth2AuthorizationRepository::new());
-        let oauth2_authorization_service =
-            Arc::new(OAuth2AuthorizationService::new(
+        let oauth2_authorization_service = Arc::new(
+            OAuth2AuthorizationService::new(
                 database.clone(),
                 oauth2_authorization_repo.clone(),
                 oauth2_client_service.clone(),
-                hasher.clone(),
-            ));
+                secret_hasher.clone(),
+            )
+            .with_config(config),
+        );
         Self {
             oauth2_client_service,
             oauth2_authorization_service,
@@ -110,6 +268,8 @@ impl OAuth2ServerModule {
 
 pub struct Container {
     pub database: Database,
+    pub hashing_pool: Arc<stardust::hash::HashingPool>,
+    pub password_hasher: Arc<PasswordHasher>,
     pub user_module: UserModule,
     pub oauth2_server_module: OAuth2ServerModule,
 }
@@ -118,25 +278,53 @@ impl Container {
     pub async fn build(
         configs: stardust::config::Config,
     ) -> stardust::Result<Arc<Self>> {
-        let database = Database::new(&configs.database).await.unwrap();
-        let hasher = Arc::new(Hasher::default());
-        let password_hasher = Arc::new(PasswordHasher::default());
+        // module sections fail the start before anything connects
+        let user_config = configs.module::<module_user::config::UserConfig>(
+            module_user::config::NAME,
+        )?;
+        let oauth2_server_config =
+            configs
+                .module::<module_oauth2_server::config::OAuth2ServerConfig>(
+                    module_oauth2_server::config::NAME,
+                )?;
+        let database = Database::new(&configs.database).await?;
+        let hashing_pool = Arc::new(stardust::hash::HashingPool::new(
+            configs.hashing.pool.clone(),
+        ));
+        let password_hasher: Arc<PasswordHasher> =
+            Arc::new(stardust::hash::BlockingHasher::new(
+                stardust::hash::password_hasher(&configs.hashing.password)?,
+                hashing_pool.clone(),
+            ));
+        let secret_hasher =
+            stardust::hash::secret_hasher(&configs.hashing.secret)?;
 
         let user_module = UserModule::new(
+            user_config,
             database.clone(),
             password_hasher.clone(),
-            hasher.clone(),
+            secret_hasher.clone(),
         );
 
-        let oauth2_server_module =
-            OAuth2ServerModule::new(database.clone(), hasher.clone());
+        let oauth2_server_module = OAuth2ServerModule::new(
+            oauth2_server_config,
+            database.clone(),
+            password_hasher.clone(),
+            secret_hasher.clone(),
+        );
 
         Ok(Arc::new(Self {
             database,
+            hashing_pool,
+            password_hasher,
             user_module,
             oauth2_server_module,
         }))
     }
+
+    pub fn migrator(&self) -> stardust::infra::migration::Migrator<Database> {
+        migrator(self)
+    }
 }
 
 impl module_user::Container for Container {
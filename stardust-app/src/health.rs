@@ -0,0 +1,142 @@
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, routing::get};
+use stardust::database::Database as _;
+
+use crate::container::Container;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Up when the database answers a ping within `PING_TIMEOUT`, with the
+/// database and hashing pool stats either way.
+async fn health(
+    State(container): State<Arc<Container>>,
+) -> (StatusCode, axum::Json<serde_json::Value>) {
+    // taken first, so the ping's own connection isn't counted as in use
+    let stats = container.database.stats();
+    let hashing = container.hashing_pool.stats();
+    let up = tokio::time::timeout(
+        PING_TIMEOUT,
+        sqlx::query("SELECT 1").execute(container.database.handle().executor()),
+    )
+    .await
+    .is_ok_and(|result| result.is_ok());
+    let wait_avg_ms = stats.acquire_wait_avg().as_secs_f64() * 1e3;
+    let wait_max_ms = stats.acquire_wait_max.as_secs_f64() * 1e3;
+    let status = if up {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        axum::Json(serde_json::json!({
+            "status": if up { "up" } else { "down" },
+            "database": {
+                "max_connections": stats.max_connections,
+                "size": stats.size,
+                "idle": stats.idle,
+                "in_use": stats.in_use,
+                "saturated": stats.is_saturated(),
+                "acquired": stats.acquired,
+                "acquire_timeouts": stats.acquire_timeouts,
+                "acquire_wait_avg_ms": wait_avg_ms,
+                "acquire_wait_max_ms": wait_max_ms,
+            },
+            "hashing_pool": {
+                "running": hashing.running,
+                "queued": hashing.queued,
+                "rejected": hashing.rejected,
+            },
+        })),
+    )
+}
+
+/// Database and hashing pool stats in the prometheus text format.
+async fn metrics(State(container): State<Arc<Container>>) -> String {
+    let stats = container.database.stats();
+    let hashing = container.hashing_pool.stats();
+    let mut body = String::new();
+    for (name, kind, help, value) in [
+        (
+            "db_pool_max_connections",
+            "gauge",
+            "Maximum connections of the pool",
+            stats.max_connections as f64,
+        ),
+        (
+            "db_pool_connections",
+            "gauge",
+            "Open connections",
+            stats.size as f64,
+        ),
+        (
+            "db_pool_idle_connections",
+            "gauge",
+            "Open connections waiting in the pool",
+            stats.idle as f64,
+        ),
+        (
+            "db_pool_in_use_connections",
+            "gauge",
+            "Connections lent to a handle",
+            stats.in_use as f64,
+        ),
+        (
+            "db_pool_acquires_total",
+            "counter",
+            "Connections acquired",
+            stats.acquired as f64,
+        ),
+        (
+            "db_pool_acquire_timeouts_total",
+            "counter",
+            "Acquires given up after the acquire timeout",
+            stats.acquire_timeouts as f64,
+        ),
+        (
+            "db_pool_acquire_wait_seconds_total",
+            "counter",
+            "Time spent waiting for a connection",
+            stats.acquire_wait_total.as_secs_f64(),
+        ),
+        (
+            "db_pool_acquire_wait_seconds_max",
+            "gauge",
+            "Longest wait for a connection",
+            stats.acquire_wait_max.as_secs_f64(),
+        ),
+        (
+            "hashing_pool_running",
+            "gauge",
+            "Hashes running on the blocking pool",
+            hashing.running as f64,
+        ),
+        (
+            "hashing_pool_queued",
+            "gauge",
+            "Hashes waiting for a slot",
+            hashing.queued as f64,
+        ),
+        (
+            "hashing_pool_rejected_total",
+            "counter",
+            "Hashes rejected on a full queue or queue timeout",
+            hashing.rejected as f64,
+        ),
+    ] {
+        let _ = writeln!(body, "# HELP {} {}", name, help);
+        let _ = writeln!(body, "# TYPE {} {}", name, kind);
+        let _ = writeln!(body, "{} {}", name, value);
+    }
+    body
+}
+
+pub fn routes(container: Arc<Container>) -> axum::Router {
+    axum::Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(metrics))
+        .with_state(container)
+}
@@ -1,27 +1,46 @@
 use axum::{handler::HandlerWithoutStateExt, http::StatusCode};
 use tower_http::services::ServeDir;
 
+pub mod admin;
+pub mod cli;
 pub mod container;
+pub mod health;
 
 #[tokio::main]
 async fn main() {
-    let config = stardust::config::Config::test_config();
-    stardust::logging::init(&config.logging);
-
-    let container = container::Container::build(config.clone()).await.unwrap();
+    let mut cli = <cli::Cli as clap::Parser>::parse();
+    let config = match cli.load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = stardust::logging::init(&config.logging) {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
 
-    stardust::infra::migration::init(container.database.clone()).await.unwrap();
-    module_user::infra::migration::migrate(
-        container.database.clone(),
-        container.clone(),
-    )
-    .await
-    .unwrap();
-    module_oauth2_server::infra::migration::migrate(container.database.clone())
-        .await
-        .unwrap();
+    // without a command, keep the dev loop of migrating then serving
+    let command =
+        cli.command.take().unwrap_or(cli::Command::Serve { migrate: true });
+    if let Err(e) = cli::run(&cli, command, config).await {
+        tracing::error!("{:?}", e);
+        eprintln!("{:?}", e);
+        std::process::exit(1);
+    }
+}
 
+pub async fn serve(
+    config: &stardust::config::Config,
+    container: std::sync::Arc<container::Container>,
+    live: tokio::sync::watch::Receiver<
+        std::sync::Arc<stardust::config::Config>,
+    >,
+) -> stardust::Result<()> {
     let router = axum::Router::new()
+        .merge(health::routes(container.clone()))
+        .merge(admin::routes(container.clone()))
         .merge(module_user::interface::http::routes(container.clone()))
         .merge(module_oauth2_server::interface::http::routes(
             container.clone(),
@@ -40,24 +59,40 @@ async fn main() {
                 },
             ),
         )
+        .layer(axum::middleware::from_fn_with_state(
+            stardust::http::ratelimit::RateLimiter::new(live.clone()),
+            stardust::http::ratelimit::rate_limit,
+        ))
         .layer(stardust::http::traceid::TraceIdLayer::default())
-        .layer(axum::middleware::from_fn(stardust::http::map_response));
+        .layer(axum::middleware::from_fn_with_state(
+            config.server.expose_internal_errors,
+            stardust::http::map_response,
+        ))
+        .layer(stardust::http::cors::cors_layer(live.clone()));
 
-    async fn handle_404() -> (StatusCode, &'static str) {
-        (StatusCode::NOT_FOUND, "Not found")
+    async fn handle_404() -> stardust::http::problem::Problem {
+        stardust::http::problem::Problem::new(StatusCode::NOT_FOUND, None)
     }
     let notfound = handle_404.into_service();
 
     let router = if let Some(httpcfg) = &config.server.http {
+        // the directory follows reloads, the mount point doesn't
+        let static_dir = tower::service_fn(move |request| {
+            let dir = live
+                .borrow()
+                .server
+                .http
+                .as_ref()
+                .map(|http| http.static_dir.clone())
+                .unwrap_or_default();
+            tower::ServiceExt::oneshot(ServeDir::new(dir), request)
+        });
         router
-            .nest_service(
-                httpcfg.static_root.as_str(),
-                ServeDir::new(httpcfg.static_dir.as_str()),
-            )
+            .nest_service(httpcfg.static_root.as_str(), static_dir)
             .fallback_service(notfound)
     } else {
         router.fallback_service(notfound)
     };
 
-    stardust::http::run_server(&config.server, router).await.unwrap();
+    stardust::http::run_server(&config.server, router).await
 }
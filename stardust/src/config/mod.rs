@@ -0,0 +1,976 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+mod watch;
+pub use watch::*;
+
+macro_rules! config_model {
+    ($($item:item)*) => {
+        $(
+            #[derive(
+                Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize,
+            )]
+            $item
+        )*
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LoggingFormat {
+    Json,
+    Pretty,
+    Full,
+    Compact,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LoggingSinkKind {
+    Stdout,
+    Stderr,
+    File,
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum LoggingRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Size, // past `max_bytes`, numbering the old files .1, .2, ...
+    Never,
+}
+
+config_model! {
+    pub struct HttpConfig {
+        pub static_root: String,
+        pub static_dir: String,
+    }
+
+    pub struct CorsConfig {
+        pub allowed_origins: Vec<String>, // exact origins, or "*"
+    }
+
+    pub struct RateLimitConfig {
+        pub requests_per_second: u32, // per client address
+        pub burst: u32, // requests allowed at once after being idle
+    }
+
+    pub struct ServerConfig {
+        pub host: String,
+        pub port: u16,
+        pub http: Option<HttpConfig>,
+        #[serde(default)]
+        pub expose_internal_errors: bool, // 5xx details, never in production
+        pub cors: Option<CorsConfig>, // no cors headers when unset
+        pub rate_limit: Option<RateLimitConfig>, // unlimited when unset
+    }
+
+    pub struct LoggingSinkConfig {
+        pub kind: LoggingSinkKind,
+        pub format: LoggingFormat,
+        pub filter: Option<String>, // narrows `logging.filter` for this sink
+        pub directory: Option<String>, // file sinks only, as below
+        pub filename: Option<String>,
+        #[serde(default)]
+        pub rotation: LoggingRotation,
+        pub max_bytes: Option<u64>, // with rotation = "size"
+        pub max_files: Option<usize>, // current one included, all when unset
+    }
+
+    pub struct LoggingConfig {
+        pub filter: String,
+        #[serde(default)]
+        pub sinks: Vec<LoggingSinkConfig>, // stdout in full format when empty
+        // moved to `sinks`, only read to be reported by `validate`
+        #[serde(default, skip_serializing)]
+        pub format: Option<serde_json::Value>,
+        #[serde(default, skip_serializing)]
+        pub file: Option<serde_json::Value>,
+    }
+
+    pub struct TransactionRetryConfig {
+        pub max_retries: u32, // reruns after a serialization error or deadlock
+        pub initial_backoff_ms: u64, // doubled on every retry
+        pub max_backoff_ms: u64,
+    }
+
+    pub struct DatabasePoolConfig {
+        pub min_connections: u32, // kept open even when idle
+        pub acquire_timeout_ms: u64, // then `Error::Timeout`
+        pub idle_timeout_ms: Option<u64>, // idle connections kept when unset
+        pub max_lifetime_ms: Option<u64>, // connections recycled after it
+        pub statement_cache_size: usize, // prepared statements per connection
+    }
+
+    pub struct DatabaseConfig {
+        pub url: String, // primary, serving writes and transactions
+        pub pool_size: u32, // max connections of each pool
+        #[serde(default)]
+        pub pool: DatabasePoolConfig,
+        #[serde(default)]
+        pub replicas: Vec<String>, // read-only urls behind `read_handle`
+        pub slow_query_ms: Option<u64>, // longer queries log at WARN
+        #[serde(default)]
+        pub retry: TransactionRetryConfig,
+    }
+
+    pub struct Argon2Config {
+        pub memory_kib: u32,
+        pub iterations: u32,
+        pub parallelism: u32,
+        pub algorithm: String, // argon2id | argon2i | argon2d
+        pub version: u32,
+        pub output_len: Option<u32>,
+        pub pepper: Option<String>, // hmac key mixed into the password
+    }
+
+    pub struct BcryptConfig {
+        pub cost: u32,
+        pub version: String, // 2a | 2b | 2x | 2y
+    }
+
+    pub struct ScryptConfig {
+        pub log_n: u8,
+        pub r: u32,
+        pub p: u32,
+        pub output_len: Option<u32>,
+    }
+
+    pub struct HmacKeyConfig {
+        pub id: String,
+        pub secret: String,
+    }
+
+    pub struct HmacConfig {
+        pub keys: Vec<HmacKeyConfig>, // first key signs new digests
+    }
+
+    pub struct PasswordHashingConfig {
+        pub algorithm: String, // noop | sha256 | argon2 | bcrypt | scrypt
+        #[serde(default)]
+        pub legacy: Vec<String>, // schemes still accepted on verify
+        pub argon2: Option<Argon2Config>,
+        pub bcrypt: Option<BcryptConfig>,
+        pub scrypt: Option<ScryptConfig>,
+    }
+
+    pub struct SecretHashingConfig {
+        pub algorithm: String, // noop | hmac
+        #[serde(default)]
+        pub legacy: Vec<String>, // schemes still found on lookup
+        pub hmac: Option<HmacConfig>,
+    }
+
+    pub struct HashingPoolConfig {
+        pub max_blocking: usize, // hashes running at the same time
+        pub queue_size: usize,   // callers waiting for a slot
+        pub queue_timeout_ms: u64,
+    }
+
+    pub struct HashingConfig {
+        pub password: PasswordHashingConfig,
+        pub secret: SecretHashingConfig,
+        #[serde(default)]
+        pub pool: HashingPoolConfig,
+    }
+
+    pub struct Config {
+        pub server: ServerConfig,
+        pub logging: LoggingConfig,
+        pub database: DatabaseConfig,
+        pub hashing: HashingConfig,
+        #[serde(default)]
+        pub modules: BTreeMap<String, serde_json::Value>, // see `module`
+    }
+}
+
+/// A module's own section, `[modules.<name>]`, read with `Config::module`.
+/// Fields left out take their `Default`, so sections are best marked
+/// `#[serde(default)]`.
+pub trait ModuleConfig: serde::de::DeserializeOwned + Default {
+    /// Invalid fields, named relative to the section.
+    fn validate(&self) -> Vec<crate::FieldError> {
+        Vec::new()
+    }
+}
+
+impl Config {
+    pub fn from_file(path: &str) -> crate::Result<Self> {
+        Self::load(path, None)
+    }
+
+    /// Loads `path`, then `<stem>.<profile>.<ext>` next to it when a
+    /// profile is given, then `APPCONFIG_*` environment variables with `__`
+    /// between sections, e.g. `APPCONFIG_DATABASE__POOL_SIZE`. A
+    /// `<key>_file` sets `<key>` to the contents of that file, for secrets
+    /// such as `database.url_file`. Every missing or invalid field is
+    /// reported in one `Error::InvalidParameter`.
+    pub fn load(path: &str, profile: Option<&str>) -> crate::Result<Self> {
+        Self::load_for(path, profile, DATABASE_SCHEMES)
+    }
+
+    /// Like `load`, also reporting a `database.url` whose scheme isn't one
+    /// of `schemes`, those of the database the binary is built with.
+    pub fn load_for(
+        path: &str,
+        profile: Option<&str>,
+        schemes: &[&str],
+    ) -> crate::Result<Self> {
+        let mut builder =
+            config::Config::builder().add_source(config::File::with_name(path));
+        if let Some(profile) = profile {
+            builder = builder.add_source(config::File::with_name(
+                &profile_path(path, profile),
+            ));
+        }
+        let raw = builder.add_source(environment()).build().map_err(|e| {
+            crate::Error::Unhandled(
+                anyhow::Error::new(e).context("build config error"),
+            )
+        })?;
+        let mut errors = Vec::new();
+        let raw = read_secret_files(raw, &mut errors)?;
+        if let Some(config) = deserialize(raw, &mut errors) {
+            config.check(schemes, &mut errors);
+            if errors.is_empty() {
+                return Ok(config);
+            }
+        }
+        Err(invalid(errors))
+    }
+
+    /// Checks values the types alone don't rule out, reporting all of them.
+    pub fn validate(&self) -> crate::Result<()> {
+        self.validate_for(DATABASE_SCHEMES)
+    }
+
+    /// `validate`, with `database.url` limited to `schemes` like `load_for`.
+    pub fn validate_for(&self, schemes: &[&str]) -> crate::Result<()> {
+        let mut errors = Vec::new();
+        self.check(schemes, &mut errors);
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(invalid(errors)),
+        }
+    }
+
+    /// `[modules.<name>]` as the module's typed config, its defaults when
+    /// the section is absent. Invalid fields are reported like `load` does,
+    /// under `modules.<name>.`.
+    pub fn module<T: ModuleConfig>(&self, name: &str) -> crate::Result<T> {
+        let prefix = |mut error: crate::FieldError| {
+            error.field = match error.field.is_empty() {
+                true => format!("modules.{}", name),
+                false => format!("modules.{}.{}", name, error.field),
+            }
+            .into();
+            error
+        };
+        let section = match self.modules.get(name) {
+            Some(section) => section.clone(),
+            None => serde_json::Value::Object(Default::default()),
+        };
+        let module = config::Config::try_from(&section)
+            .and_then(|section| section.try_deserialize::<T>())
+            .map_err(|e| invalid(vec![prefix(describe(e).0)]))?;
+        let errors: Vec<_> =
+            module.validate().into_iter().map(prefix).collect();
+        match errors.is_empty() {
+            true => Ok(module),
+            false => Err(invalid(errors)),
+        }
+    }
+
+    fn check(&self, schemes: &[&str], errors: &mut Vec<crate::FieldError>) {
+        let mut check = |ok: bool, field: &str, code: &'static str, message| {
+            if !ok {
+                errors.push(crate::FieldError::new(
+                    field.to_owned(),
+                    code,
+                    message,
+                ));
+            }
+        };
+
+        let server = &self.server;
+        check(
+            !server.host.is_empty(),
+            "server.host",
+            "required",
+            "empty".into(),
+        );
+        if let Some(http) = &server.http {
+            check(
+                http.static_root.starts_with('/'),
+                "server.http.static_root",
+                "invalid_format",
+                "must start with /".into(),
+            );
+        }
+
+        if let Some(cors) = &server.cors {
+            for (i, origin) in cors.allowed_origins.iter().enumerate() {
+                check(
+                    origin == "*"
+                        || origin.starts_with("http://")
+                        || origin.starts_with("https://"),
+                    &format!("server.cors.allowed_origins[{}]", i),
+                    "invalid_format",
+                    "expected \"*\" or an http(s) origin".into(),
+                );
+            }
+        }
+        if let Some(rate_limit) = &server.rate_limit {
+            check(
+                rate_limit.requests_per_second > 0,
+                "server.rate_limit.requests_per_second",
+                "out_of_range",
+                "must be at least 1".into(),
+            );
+            check(
+                rate_limit.burst > 0,
+                "server.rate_limit.burst",
+                "out_of_range",
+                "must be at least 1".into(),
+            );
+        }
+
+        let logging = &self.logging;
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&logging.filter)
+        {
+            check(false, "logging.filter", "invalid_format", e.to_string());
+        }
+        for (key, value) in
+            [("format", &logging.format), ("file", &logging.file)]
+        {
+            check(
+                value.is_none(),
+                &format!("logging.{}", key),
+                "unsupported",
+                "moved to logging.sinks".into(),
+            );
+        }
+        for (i, sink) in logging.sinks.iter().enumerate() {
+            let field = |key: &str| format!("logging.sinks[{}].{}", i, key);
+            if let Some(Err(e)) =
+                sink.filter.as_ref().map(tracing_subscriber::EnvFilter::try_new)
+            {
+                check(false, &field("filter"), "invalid_format", e.to_string());
+            }
+            if sink.kind != LoggingSinkKind::File {
+                continue;
+            }
+            for (key, value) in
+                [("directory", &sink.directory), ("filename", &sink.filename)]
+            {
+                check(
+                    value.as_ref().is_some_and(|value| !value.is_empty()),
+                    &field(key),
+                    "required",
+                    "required for file sinks".into(),
+                );
+            }
+            check(
+                sink.rotation != LoggingRotation::Size
+                    || sink.max_bytes.is_some_and(|max_bytes| max_bytes > 0),
+                &field("max_bytes"),
+                "required",
+                "must be at least 1 with rotation = \"size\"".into(),
+            );
+            check(
+                sink.max_files != Some(0),
+                &field("max_files"),
+                "out_of_range",
+                "must be at least 1".into(),
+            );
+        }
+
+        let database = &self.database;
+        fn scheme(url: &str) -> Option<&str> {
+            url.split_once(':').map(|(scheme, _)| scheme)
+        }
+        let url_scheme = scheme(&database.url)
+            .filter(|scheme| DATABASE_SCHEMES.contains(scheme));
+        check(
+            url_scheme.is_some(),
+            "database.url",
+            "invalid_format",
+            "expected a postgres, sqlite or mysql url".into(),
+        );
+        check(
+            url_scheme.is_none_or(|scheme| schemes.contains(&scheme)),
+            "database.url",
+            "unsupported",
+            format!("this build expects a {} url", schemes.join(" or ")),
+        );
+        let postgres = matches!(url_scheme, Some("postgres" | "postgresql"));
+        check(
+            postgres || database.replicas.is_empty(),
+            "database.replicas",
+            "unsupported",
+            "only postgres databases have replicas".into(),
+        );
+        for (i, replica) in database.replicas.iter().enumerate() {
+            check(
+                !postgres || scheme(replica) == url_scheme,
+                &format!("database.replicas[{}]", i),
+                "invalid_format",
+                "expected a url of the primary's database".into(),
+            );
+        }
+        check(
+            database.pool_size > 0,
+            "database.pool_size",
+            "out_of_range",
+            "must be at least 1".into(),
+        );
+        check(
+            database.pool.min_connections <= database.pool_size,
+            "database.pool.min_connections",
+            "out_of_range",
+            "must not exceed pool_size".into(),
+        );
+        check(
+            database.pool.acquire_timeout_ms > 0,
+            "database.pool.acquire_timeout_ms",
+            "out_of_range",
+            "must be at least 1".into(),
+        );
+        check(
+            database.retry.initial_backoff_ms <= database.retry.max_backoff_ms,
+            "database.retry.initial_backoff_ms",
+            "out_of_range",
+            "must not exceed max_backoff_ms".into(),
+        );
+
+        let password = &self.hashing.password;
+        for (field, scheme) in
+            std::iter::once(("algorithm", &password.algorithm))
+                .chain(password.legacy.iter().map(|legacy| ("legacy", legacy)))
+        {
+            check(
+                scheme.parse::<crate::hash::HashScheme>().is_ok(),
+                &format!("hashing.password.{}", field),
+                "unsupported",
+                format!("unknown scheme {}", scheme),
+            );
+        }
+        if let Some(argon2) = &password.argon2 {
+            check(
+                ["argon2id", "argon2i", "argon2d"]
+                    .contains(&argon2.algorithm.as_str()),
+                "hashing.password.argon2.algorithm",
+                "unsupported",
+                "expected argon2id, argon2i or argon2d".into(),
+            );
+        }
+        if let Some(bcrypt) = &password.bcrypt {
+            check(
+                ["2a", "2b", "2x", "2y"].contains(&bcrypt.version.as_str()),
+                "hashing.password.bcrypt.version",
+                "unsupported",
+                "expected 2a, 2b, 2x or 2y".into(),
+            );
+        }
+
+        let secret = &self.hashing.secret;
+        for (field, scheme) in std::iter::once(("algorithm", &secret.algorithm))
+            .chain(secret.legacy.iter().map(|legacy| ("legacy", legacy)))
+        {
+            check(
+                ["noop", "hmac"].contains(&scheme.as_str()),
+                &format!("hashing.secret.{}", field),
+                "unsupported",
+                "expected noop or hmac".into(),
+            );
+        }
+        let uses_hmac = std::iter::once(&secret.algorithm)
+            .chain(&secret.legacy)
+            .any(|scheme| scheme == "hmac");
+        if uses_hmac {
+            match &secret.hmac {
+                Some(hmac) => {
+                    check(
+                        !hmac.keys.is_empty(),
+                        "hashing.secret.hmac.keys",
+                        "required",
+                        "at least one key signs new digests".into(),
+                    );
+                    for (i, key) in hmac.keys.iter().enumerate() {
+                        check(
+                            !key.secret.is_empty(),
+                            &format!("hashing.secret.hmac.keys[{}].secret", i),
+                            "required",
+                            "empty".into(),
+                        );
+                    }
+                }
+                None => check(
+                    false,
+                    "hashing.secret.hmac",
+                    "required",
+                    "required for hmac".into(),
+                ),
+            }
+        }
+        check(
+            self.hashing.pool.max_blocking > 0,
+            "hashing.pool.max_blocking",
+            "out_of_range",
+            "must be at least 1".into(),
+        );
+    }
+
+    pub fn test_config_path() -> String {
+        Path::new(&crate::utils::manifest_dir().unwrap())
+            .join("..")
+            .join("testenv")
+            .join("config.test.toml")
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn test_config() -> Self {
+        let mut config = Config::from_file(&Self::test_config_path()).unwrap();
+        config.logging.sinks.retain(|sink| sink.kind != LoggingSinkKind::File);
+        config
+    }
+}
+
+/// `database.url` schemes of every backend.
+const DATABASE_SCHEMES: &[&str] =
+    &["postgres", "postgresql", "sqlite", "mysql"];
+
+/// `APPCONFIG_` then sections joined by `__`, so single underscores stay
+/// in snake_case keys.
+fn environment() -> config::Environment {
+    config::Environment::with_prefix("APPCONFIG")
+        .prefix_separator("_")
+        .separator("__")
+}
+
+fn invalid(fields: Vec<crate::FieldError>) -> crate::Error {
+    crate::Error::InvalidParameter(crate::ErrorDetail {
+        fields,
+        ..crate::ErrorDetail::new("config.invalid", "invalid config")
+    })
+}
+
+/// Replaces every `<key>_file` with `<key>` set to the file's contents,
+/// without the trailing newline.
+fn read_secret_files(
+    raw: config::Config,
+    errors: &mut Vec<crate::FieldError>,
+) -> crate::Result<config::Config> {
+    fn walk(
+        path: String,
+        value: &serde_json::Value,
+        found: &mut Vec<(String, String)>,
+    ) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    let key_path = match path.is_empty() {
+                        true => key.clone(),
+                        false => format!("{}.{}", path, key),
+                    };
+                    match (key.strip_suffix("_file"), value) {
+                        (Some(key), serde_json::Value::String(file))
+                            if !key.is_empty() =>
+                        {
+                            found.push((key_path, file.clone()));
+                        }
+                        _ => walk(key_path, value, found),
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    walk(format!("{}[{}]", path, i), value, found);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let tree = raw.clone().try_deserialize::<serde_json::Value>();
+    let mut found = Vec::new();
+    walk(String::new(), &tree.unwrap_or_default(), &mut found);
+    if found.is_empty() {
+        return Ok(raw);
+    }
+    let mut builder = config::Config::builder().add_source(raw);
+    for (key_path, file) in found {
+        match std::fs::read_to_string(&file) {
+            Ok(contents) => {
+                let key = &key_path[..key_path.len() - "_file".len()];
+                let contents = contents.trim_end_matches(['\n', '\r']);
+                builder = builder.set_override(key, contents).map_err(|e| {
+                    crate::Error::Unhandled(anyhow::Error::new(e))
+                })?;
+            }
+            Err(e) => errors.push(crate::FieldError::new(
+                key_path,
+                "unreadable",
+                format!("{}: {}", file, e),
+            )),
+        }
+    }
+    builder.build().map_err(|e| {
+        crate::Error::Unhandled(
+            anyhow::Error::new(e).context("build config error"),
+        )
+    })
+}
+
+/// The field error of a deserialization error, and whether it names a key.
+fn describe(error: config::ConfigError) -> (crate::FieldError, bool) {
+    match error {
+        config::ConfigError::NotFound(key) => {
+            (crate::FieldError::new(key, "required", "missing"), true)
+        }
+        config::ConfigError::Type {
+            key: Some(key),
+            unexpected,
+            expected,
+            ..
+        } => (
+            crate::FieldError::new(
+                key,
+                "invalid_type",
+                format!("invalid type: {}, expected {}", unexpected, expected),
+            ),
+            true,
+        ),
+        config::ConfigError::At {
+            key: Some(key),
+            error,
+            ..
+        } => (
+            crate::FieldError::new(key, "invalid", error.to_string()),
+            true,
+        ),
+        error => (
+            crate::FieldError::new("", "invalid", error.to_string()),
+            false,
+        ),
+    }
+}
+
+/// Deserialization stops at the first bad key, so each one is reported and
+/// replaced by a placeholder to reach the next. Keys under a reported one
+/// aren't reported again.
+fn deserialize(
+    mut raw: config::Config,
+    errors: &mut Vec<crate::FieldError>,
+) -> Option<Config> {
+    const MAX_ERRORS: usize = 64;
+    let placeholders = || -> [config::Value; 3] {
+        [
+            config::Map::<String, config::Value>::new().into(),
+            0i64.into(),
+            Vec::<config::Value>::new().into(),
+        ]
+    };
+
+    let mut reported: Vec<String> = Vec::new();
+    let mut attempts = std::collections::HashMap::new();
+    for _ in 0..MAX_ERRORS * 3 {
+        let error = match raw.clone().try_deserialize::<Config>() {
+            Ok(config) => return reported.is_empty().then_some(config),
+            Err(error) => error,
+        };
+        let (error, keyed) = describe(error);
+        if !keyed {
+            errors.push(error);
+            return None;
+        }
+        let key = error.field.to_string();
+        let attempt = attempts.entry(key.clone()).or_insert(0);
+        if *attempt == 0 {
+            let nested = reported.iter().any(|reported| {
+                key.strip_prefix(reported.as_str())
+                    .is_some_and(|rest| rest.starts_with(['.', '[']))
+            });
+            if !nested {
+                errors.push(error);
+                reported.push(key.clone());
+            }
+        }
+        let placeholder = placeholders().into_iter().nth(*attempt)?;
+        raw = config::Config::builder()
+            .add_source(raw)
+            .set_override(key.as_str(), placeholder)
+            .and_then(|builder| builder.build())
+            .ok()?;
+        *attempt += 1;
+        if reported.len() >= MAX_ERRORS {
+            return None;
+        }
+    }
+    None
+}
+
+fn profile_path(path: &str, profile: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, profile, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, profile),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_profile_path() {
+        assert_eq!(
+            profile_path("testenv/config.toml", "prod"),
+            "testenv/config.prod.toml"
+        );
+        assert_eq!(profile_path("config", "prod"), "config.prod");
+    }
+
+    #[test]
+    fn test_config() {
+        let config = Config::test_config();
+        println!("{:?}", config);
+    }
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("stardust-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn fields(error: crate::Error) -> Vec<(String, String)> {
+        assert_eq!(error.code(), "config.invalid");
+        error
+            .fields()
+            .iter()
+            .map(|field| (field.field.to_string(), field.code.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_environment() {
+        let raw = config::Config::builder()
+            .add_source(config::File::from_str(
+                "[database]\npool_size = 1\n",
+                config::FileFormat::Toml,
+            ))
+            .add_source(
+                environment().source(Some(
+                    [
+                        ("APPCONFIG_DATABASE__POOL_SIZE", "7"),
+                        ("APPCONFIG_DATABASE__SLOW_QUERY_MS", "100"),
+                        ("OTHER_DATABASE__POOL_SIZE", "9"),
+                    ]
+                    .into_iter()
+                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                    .collect(),
+                )),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(raw.get_int("database.pool_size").unwrap(), 7);
+        assert_eq!(raw.get_int("database.slow_query_ms").unwrap(), 100);
+    }
+
+    #[test]
+    fn test_profile_and_secret_file() {
+        let dir = temp_dir();
+        let path = dir.join("config.toml");
+        std::fs::copy(
+            Path::new(&crate::utils::manifest_dir().unwrap())
+                .join("../testenv/config.test.toml"),
+            &path,
+        )
+        .unwrap();
+        std::fs::write(dir.join("db_url"), "sqlite::memory:\n").unwrap();
+        std::fs::write(
+            dir.join("config.prod.toml"),
+            format!(
+                "[database]\nurl_file = {:?}\npool_size = 3\n",
+                dir.join("db_url").to_str().unwrap()
+            ),
+        )
+        .unwrap();
+
+        let path = path.to_str().unwrap();
+        let base = Config::load(path, None).unwrap();
+        assert!(base.database.url.starts_with("postgres://"));
+        let prod = Config::load(path, Some("prod")).unwrap();
+        assert_eq!(prod.database.url, "sqlite::memory:");
+        assert_eq!(prod.database.pool_size, 3);
+        assert_eq!(prod.server.port, base.server.port);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_reports_every_field() {
+        let dir = temp_dir();
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[server]
+host = "0.0.0.0"
+
+[logging]
+filter = "info"
+
+[database]
+url_file = "/nonexistent/stardust/db_url"
+pool_size = "many"
+"#,
+        )
+        .unwrap();
+
+        let error = Config::load(path.to_str().unwrap(), None).unwrap_err();
+        let mut fields = fields(error);
+        fields.sort();
+        assert_eq!(
+            fields,
+            [
+                ("database.pool_size", "invalid_type"),
+                ("database.url", "required"),
+                ("database.url_file", "unreadable"),
+                ("hashing", "required"),
+                ("server.port", "required"),
+            ]
+            .map(|(field, code)| (field.to_owned(), code.to_owned()))
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::test_config();
+        config.validate().unwrap();
+
+        config.database.url = "redis://localhost".into();
+        config.database.pool_size = 0;
+        config.logging.filter = "stardust=loud".into();
+        config.logging.file = Some(serde_json::json!({"directory": "logs"}));
+        config.logging.sinks = vec![LoggingSinkConfig {
+            kind: LoggingSinkKind::File,
+            format: LoggingFormat::Json,
+            filter: Some("stardust=loud".into()),
+            directory: Some("logs".into()),
+            filename: None,
+            rotation: LoggingRotation::Size,
+            max_bytes: None,
+            max_files: Some(0),
+        }];
+        config.hashing.password.legacy = vec!["md5".into()];
+        config.hashing.secret.legacy = vec!["md5".into()];
+        config.hashing.secret.hmac = None;
+        assert_eq!(
+            fields(config.validate().unwrap_err()),
+            [
+                ("logging.filter", "invalid_format"),
+                ("logging.file", "unsupported"),
+                ("logging.sinks[0].filter", "invalid_format"),
+                ("logging.sinks[0].filename", "required"),
+                ("logging.sinks[0].max_bytes", "required"),
+                ("logging.sinks[0].max_files", "out_of_range"),
+                ("database.url", "invalid_format"),
+                ("database.pool_size", "out_of_range"),
+                ("hashing.password.legacy", "unsupported"),
+                ("hashing.secret.legacy", "unsupported"),
+                ("hashing.secret.hmac", "required"),
+            ]
+            .map(|(field, code)| (field.to_owned(), code.to_owned()))
+        );
+
+        // replicas are postgres only, and the binary picks one backend
+        let mut config = Config::test_config();
+        config.database.url = "sqlite::memory:".into();
+        config.database.replicas = vec!["sqlite::memory:".into()];
+        assert_eq!(
+            fields(config.validate_for(&["postgres"]).unwrap_err()),
+            [
+                ("database.url", "unsupported"),
+                ("database.replicas", "unsupported"),
+            ]
+            .map(|(field, code)| (field.to_owned(), code.to_owned()))
+        );
+        config.database.replicas.clear();
+        config.validate_for(&["sqlite"]).unwrap();
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    #[serde(default)]
+    struct SampleConfig {
+        ttl_secs: u64,
+        name: String,
+    }
+
+    impl Default for SampleConfig {
+        fn default() -> Self {
+            Self {
+                ttl_secs: 60,
+                name: "sample".into(),
+            }
+        }
+    }
+
+    impl ModuleConfig for SampleConfig {
+        fn validate(&self) -> Vec<crate::FieldError> {
+            match self.ttl_secs {
+                0 => vec![crate::FieldError::new(
+                    "ttl_secs",
+                    "out_of_range",
+                    "must be at least 1",
+                )],
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_module() {
+        let mut config = Config::test_config();
+        assert_eq!(
+            config.module::<SampleConfig>("sample").unwrap(),
+            SampleConfig::default()
+        );
+
+        // env vars arrive as strings
+        config
+            .modules
+            .insert("sample".into(), serde_json::json!({ "ttl_secs": "30" }));
+        assert_eq!(
+            config.module::<SampleConfig>("sample").unwrap(),
+            SampleConfig {
+                ttl_secs: 30,
+                name: "sample".into()
+            }
+        );
+
+        for (ttl_secs, code) in [
+            (serde_json::json!("soon"), "invalid_type"),
+            (serde_json::json!(0), "out_of_range"),
+        ] {
+            config.modules.insert(
+                "sample".into(),
+                serde_json::json!({ "ttl_secs": ttl_secs }),
+            );
+            assert_eq!(
+                fields(config.module::<SampleConfig>("sample").unwrap_err()),
+                [("modules.sample.ttl_secs".to_owned(), code.to_owned())]
+            );
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Live config. `ConfigWatcher` reloads the files on SIGHUP or when they
+//! change, validates them again and publishes the result on a
+//! `tokio::sync::watch` channel. Subsystems that can apply a change while
+//! running subscribe to it; every other field keeps its startup value.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::watch;
+
+use super::Config;
+
+/// Fields applied while running, with everything under them.
+pub const HOT_FIELDS: &[&str] = &[
+    "logging.filter",
+    "server.http.static_dir",
+    "server.cors",
+    "server.rate_limit",
+];
+
+pub struct ConfigWatcher {
+    path: String,
+    profile: Option<String>,
+    // last files read, so a rejected change is only warned about once
+    loaded: Mutex<Config>,
+    sender: watch::Sender<Arc<Config>>,
+}
+
+impl ConfigWatcher {
+    /// Watches the files `config` was loaded from with `Config::load`.
+    /// Reloads are compared against `config`, not the files as they are
+    /// now, so an edit made since it was loaded isn't missed.
+    pub fn new(path: &str, profile: Option<&str>, config: Config) -> Self {
+        Self {
+            path: path.to_owned(),
+            profile: profile.map(|profile| profile.to_owned()),
+            loaded: Mutex::new(config.clone()),
+            sender: watch::Sender::new(Arc::new(config)),
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.sender.subscribe()
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.sender.borrow().clone()
+    }
+
+    /// Reads the files again and publishes the hot fields that changed
+    /// since the last read, `Ok(false)` when there were none. Changed
+    /// fields that aren't hot are logged and left as they are.
+    pub fn reload(&self) -> crate::Result<bool> {
+        let loaded = Config::load(&self.path, self.profile.as_deref())?;
+        let previous = std::mem::replace(
+            &mut *self.loaded.lock().unwrap(),
+            loaded.clone(),
+        );
+        let (hot, rejected): (Vec<_>, Vec<_>) =
+            changed_fields(&previous, &loaded)
+                .into_iter()
+                .partition(|field| is_hot(field));
+        for field in rejected {
+            tracing::warn!(
+                "config: {} changed, restart to apply it; keeping the running value",
+                field
+            );
+        }
+        if hot.is_empty() {
+            return Ok(false);
+        }
+
+        let mut next = to_value(&self.current());
+        let loaded = to_value(&loaded);
+        for field in hot.iter() {
+            let pointer = pointer(field);
+            if let (Some(slot), Some(value)) =
+                (next.pointer_mut(&pointer), loaded.pointer(&pointer))
+            {
+                *slot = value.clone();
+            }
+        }
+        let next: Config = serde_json::from_value(next).map_err(|e| {
+            crate::Error::Unhandled(
+                anyhow::Error::new(e).context("apply config error"),
+            )
+        })?;
+        tracing::info!("config: applied {}", hot.join(", "));
+        self.sender.send_replace(Arc::new(next));
+        Ok(true)
+    }
+
+    /// Reloads on SIGHUP and whenever the modification time of a config
+    /// file changes, checked every `interval`. Invalid files are logged and
+    /// skipped until they are fixed.
+    pub fn spawn(
+        self: Arc<Self>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            let mut hangup = tokio::signal::unix::signal(
+                tokio::signal::unix::SignalKind::hangup(),
+            )
+            .ok();
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(
+                tokio::time::MissedTickBehavior::Delay,
+            );
+            let mut modified = self.modified();
+            loop {
+                #[cfg(unix)]
+                let hangup = async {
+                    match hangup.as_mut() {
+                        Some(hangup) => hangup.recv().await,
+                        None => std::future::pending().await,
+                    }
+                };
+                #[cfg(not(unix))]
+                let hangup = std::future::pending::<Option<()>>();
+
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        let now = self.modified();
+                        if now == modified {
+                            continue;
+                        }
+                        modified = now;
+                    }
+                    _ = hangup => tracing::info!("config: SIGHUP"),
+                }
+                if let Err(e) = self.reload() {
+                    tracing::error!("config: reload failed: {}", e);
+                }
+            }
+        })
+    }
+
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        let mut paths = vec![self.path.clone()];
+        if let Some(profile) = &self.profile {
+            paths.push(super::profile_path(&self.path, profile));
+        }
+        paths
+            .iter()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+fn is_hot(field: &str) -> bool {
+    HOT_FIELDS.iter().any(|hot| {
+        field
+            .strip_prefix(hot)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+fn to_value(config: &Config) -> serde_json::Value {
+    serde_json::to_value(config).unwrap_or_default()
+}
+
+fn pointer(field: &str) -> String {
+    field.split('.').fold(String::new(), |pointer, key| pointer + "/" + key)
+}
+
+/// Dotted paths of the values that differ, stopping at the first level
+/// where one side isn't a table, e.g. `server.port` or `server.http`.
+fn changed_fields(previous: &Config, loaded: &Config) -> Vec<String> {
+    fn walk(
+        path: &str,
+        previous: &serde_json::Value,
+        loaded: &serde_json::Value,
+        changed: &mut Vec<String>,
+    ) {
+        match (previous, loaded) {
+            (
+                serde_json::Value::Object(previous),
+                serde_json::Value::Object(loaded),
+            ) => {
+                let keys = previous.keys().chain(
+                    loaded.keys().filter(|key| !previous.contains_key(*key)),
+                );
+                for key in keys {
+                    let null = serde_json::Value::Null;
+                    let field = match path.is_empty() {
+                        true => key.clone(),
+                        false => format!("{}.{}", path, key),
+                    };
+                    walk(
+                        &field,
+                        previous.get(key).unwrap_or(&null),
+                        loaded.get(key).unwrap_or(&null),
+                        changed,
+                    );
+                }
+            }
+            (previous, loaded) if previous != loaded => {
+                changed.push(path.to_owned())
+            }
+            _ => {}
+        }
+    }
+
+    let mut changed = Vec::new();
+    walk("", &to_value(previous), &to_value(loaded), &mut changed);
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn write_config(path: &Path, replace: &[(&str, &str)]) {
+        let mut toml = std::fs::read_to_string(
+            Path::new(&crate::utils::manifest_dir().unwrap())
+                .join("../testenv/config.test.toml"),
+        )
+        .unwrap();
+        for (from, to) in replace {
+            assert!(toml.contains(from), "{}", from);
+            toml = toml.replace(from, to);
+        }
+        std::fs::write(path, toml).unwrap();
+    }
+
+    #[test]
+    fn test_is_hot() {
+        assert!(is_hot("logging.filter"));
+        assert!(is_hot("server.cors.allowed_origins"));
+        assert!(!is_hot("server.corsair"));
+        assert!(!is_hot("server.port"));
+        assert!(!is_hot("server.http"));
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let dir = std::env::temp_dir()
+            .join(format!("stardust-watch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        write_config(&path, &[]);
+        let path = path.to_str().unwrap();
+
+        let watcher =
+            ConfigWatcher::new(path, None, Config::load(path, None).unwrap());
+        let mut receiver = watcher.subscribe();
+        assert!(!watcher.reload().unwrap());
+
+        // the port needs a restart, the filter and origins don't
+        write_config(
+            Path::new(path),
+            &[
+                ("filter = \"debug\"", "filter = \"info\""),
+                ("port = 5299", "port = 5300"),
+                (
+                    "expose_internal_errors = true",
+                    "expose_internal_errors = true\n\
+                     [server.cors]\n\
+                     allowed_origins = [\"https://example.com\"]",
+                ),
+            ],
+        );
+        assert!(watcher.reload().unwrap());
+        assert!(receiver.has_changed().unwrap());
+        let config = receiver.borrow_and_update().clone();
+        assert_eq!(config.logging.filter, "info");
+        assert_eq!(config.server.port, 5299);
+        assert_eq!(
+            config.server.cors.as_ref().unwrap().allowed_origins,
+            ["https://example.com"]
+        );
+
+        // invalid files leave the running config alone
+        write_config(Path::new(path), &[("pool_size = 1", "pool_size = 0")]);
+        assert!(watcher.reload().is_err());
+        assert!(!receiver.has_changed().unwrap());
+        assert_eq!(watcher.current().logging.filter, "info");
+
+        // an edit made before the watcher starts is applied on reload
+        write_config(Path::new(path), &[]);
+        let config = Config::load(path, None).unwrap();
+        write_config(
+            Path::new(path),
+            &[("filter = \"debug\"", "filter = \"warn\"")],
+        );
+        let watcher = ConfigWatcher::new(path, None, config);
+        assert!(watcher.reload().unwrap());
+        assert_eq!(watcher.current().logging.filter, "warn");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+//! Spans around the queries run through the backends' `Executor`s, and the
+//! slow query log.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_core::{Stream, future::BoxFuture, stream::BoxStream};
+
+/// Operation and first table of a statement, e.g. `("SELECT",
+/// Some("stardust_user"))`, good enough for span names.
+pub fn describe(sql: &str) -> (String, Option<&str>) {
+    let mut words = sql.split_whitespace();
+    let operation = words.next().unwrap_or_default().to_ascii_uppercase();
+    let keyword = match operation.as_str() {
+        "SELECT" | "DELETE" | "WITH" => "FROM",
+        "INSERT" | "REPLACE" => "INTO",
+        "UPDATE" => "UPDATE",
+        _ => return (operation, None),
+    };
+    let table = if keyword == "UPDATE" {
+        words.next()
+    } else {
+        words
+            .by_ref()
+            .find(|word| word.eq_ignore_ascii_case(keyword))
+            .and_then(|_| words.next())
+    };
+    let table = table
+        .map(|table| table.split(['(', ',', ';']).next().unwrap_or_default())
+        .map(|table| table.trim_matches(['"', '`']))
+        .filter(|table| !table.is_empty());
+    (operation, table)
+}
+
+struct Query<'q> {
+    span: tracing::Span,
+    sql: &'q str,
+    start: Instant,
+    rows: u64,
+    // from `database.slow_query_ms` of the database running it
+    slow_query: Option<Duration>,
+}
+
+impl<'q> Query<'q> {
+    fn new(
+        system: &'static str,
+        sql: &'q str,
+        slow_query: Option<Duration>,
+    ) -> Self {
+        let (operation, table) = describe(sql);
+        let span = tracing::debug_span!(
+            "db.query",
+            db.system = system,
+            db.operation = %operation,
+            db.table = table.unwrap_or_default(),
+            db.rows = tracing::field::Empty,
+            elapsed_ms = tracing::field::Empty,
+        );
+        Self {
+            span,
+            sql,
+            start: Instant::now(),
+            rows: 0,
+            slow_query,
+        }
+    }
+
+    fn finish(self) {
+        let elapsed = self.start.elapsed();
+        let elapsed_ms = elapsed.as_secs_f64() * 1e3;
+        self.span.record("db.rows", self.rows);
+        self.span.record("elapsed_ms", elapsed_ms);
+        if self.slow_query.is_some_and(|threshold| elapsed >= threshold) {
+            tracing::warn!(
+                parent: &self.span,
+                trace_id = crate::http::traceid::current().unwrap_or_default(),
+                rows = self.rows,
+                elapsed_ms,
+                "slow query: {}",
+                self.sql.split_whitespace().collect::<Vec<_>>().join(" ")
+            );
+        }
+    }
+}
+
+/// Rows of a `fetch_many`, finishing the query on its end, its first error
+/// or when the caller stops reading.
+struct QueryStream<'e, DB: sqlx::Database> {
+    inner: BoxStream<'e, Result<super::Fetched<DB>, sqlx::Error>>,
+    query: Option<Query<'e>>,
+    rows_affected: fn(&DB::QueryResult) -> u64,
+}
+
+impl<DB: sqlx::Database> Stream for QueryStream<'_, DB> {
+    type Item = Result<super::Fetched<DB>, sqlx::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let Some(query) = &mut this.query else {
+            return Poll::Ready(None);
+        };
+        let item = {
+            let _entered = query.span.enter();
+            this.inner.as_mut().poll_next(cx)
+        };
+        match &item {
+            Poll::Ready(Some(Ok(sqlx::Either::Left(result)))) => {
+                query.rows += (this.rows_affected)(result);
+            }
+            Poll::Ready(Some(Ok(sqlx::Either::Right(_)))) => query.rows += 1,
+            Poll::Ready(_) => {
+                if let Some(query) = this.query.take() {
+                    query.finish();
+                }
+            }
+            Poll::Pending => {}
+        }
+        item
+    }
+}
+
+impl<DB: sqlx::Database> Drop for QueryStream<'_, DB> {
+    fn drop(&mut self) {
+        if let Some(query) = self.query.take() {
+            query.finish();
+        }
+    }
+}
+
+pub(crate) fn fetch_many<'e, DB: sqlx::Database>(
+    system: &'static str,
+    sql: &'e str,
+    slow_query: Option<Duration>,
+    inner: BoxStream<'e, Result<super::Fetched<DB>, sqlx::Error>>,
+    rows_affected: fn(&DB::QueryResult) -> u64,
+) -> BoxStream<'e, Result<super::Fetched<DB>, sqlx::Error>> {
+    Box::pin(QueryStream::<DB> {
+        inner,
+        query: Some(Query::new(system, sql, slow_query)),
+        rows_affected,
+    })
+}
+
+pub(crate) fn fetch_optional<'e, DB: sqlx::Database>(
+    system: &'static str,
+    sql: &'e str,
+    slow_query: Option<Duration>,
+    inner: BoxFuture<'e, Result<Option<DB::Row>, sqlx::Error>>,
+) -> BoxFuture<'e, Result<Option<DB::Row>, sqlx::Error>> {
+    use tracing::Instrument;
+
+    let mut query = Query::new(system, sql, slow_query);
+    Box::pin(async move {
+        let result = inner.instrument(query.span.clone()).await;
+        query.rows = matches!(result, Ok(Some(_))) as u64;
+        query.finish();
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            super::describe("select * from stardust_user where id = $1"),
+            ("SELECT".into(), Some("stardust_user"))
+        );
+        assert_eq!(
+            super::describe(
+                "INSERT INTO \"stardust_apikey\"(user_id, key_hash) VALUES"
+            ),
+            ("INSERT".into(), Some("stardust_apikey"))
+        );
+        assert_eq!(
+            super::describe("UPDATE `stardust_user` SET status = ?"),
+            ("UPDATE".into(), Some("stardust_user"))
+        );
+        assert_eq!(
+            super::describe("\n  DELETE FROM stardust_lock WHERE name = $1"),
+            ("DELETE".into(), Some("stardust_lock"))
+        );
+        assert_eq!(super::describe("SELECT 1"), ("SELECT".into(), None));
+        assert_eq!(super::describe("BEGIN"), ("BEGIN".into(), None));
+    }
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_query() {
+        let captured = Captured::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_writer({
+                    let captured = captured.clone();
+                    move || captured.clone()
+                })
+                .with_max_level(tracing::Level::DEBUG)
+                .with_ansi(false)
+                .finish(),
+        );
+
+        crate::http::traceid::scope("trace-1".into(), async {
+            let mut query = super::Query::new(
+                "sqlite",
+                "SELECT * FROM stardust_user WHERE id = 1",
+                Some(Duration::from_secs(60)),
+            );
+            query.rows = 1;
+            query.finish();
+            super::Query::new(
+                "sqlite",
+                "UPDATE stardust_user SET id = 2",
+                Some(Duration::ZERO),
+            )
+            .finish();
+            super::Query::new("sqlite", "DELETE FROM stardust_user", None)
+                .finish();
+        })
+        .await;
+
+        let output =
+            String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 1, "{}", output);
+        assert!(lines[0].contains("WARN"));
+        assert!(lines[0].contains("db.operation=UPDATE"));
+        assert!(lines[0].contains("db.table=\"stardust_user\""));
+        assert!(lines[0].contains("trace_id=\"trace-1\""));
+        assert!(lines[0].contains("slow query: UPDATE stardust_user"));
+    }
+}
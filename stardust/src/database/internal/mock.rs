@@ -1,3 +1,5 @@
+use futures_core::future::BoxFuture;
+
 #[derive(Debug, Clone, Default)]
 pub struct Database {}
 
@@ -11,11 +13,18 @@ pub struct Handle<'a> {
     pub db: &'a Database,
 }
 
+impl<'a> Handle<'a> {
+    pub async fn savepoint(&mut self) -> crate::Result<Handle<'_>> {
+        Ok(Handle { db: self.db })
+    }
+}
+
 impl crate::database::Database for Database {
     type Handle<'h>
         = Handle<'h>
     where
         Self: 'h;
+    type Lock = Lock;
 
     fn handle(&self) -> Self::Handle<'_> {
         Handle { db: self }
@@ -24,6 +33,49 @@ impl crate::database::Database for Database {
     async fn tx_handle(&self) -> crate::Result<Self::Handle<'_>> {
         Ok(Handle { db: self })
     }
+
+    async fn transaction<'a, T, F>(&'a self, f: F) -> crate::Result<T>
+    where
+        T: Send,
+        F: for<'h> FnMut(
+                crate::database::Tx<'h, 'a, Self::Handle<'a>>,
+            ) -> BoxFuture<'h, crate::Result<T>>
+            + Send,
+    {
+        crate::database::internal::transaction(self, &Default::default(), f)
+            .await
+    }
+
+    async fn savepoint<'s>(
+        &'s self,
+        handle: &'s mut Self::Handle<'_>,
+    ) -> crate::Result<Self::Handle<'s>> {
+        handle.savepoint().await
+    }
+
+    async fn lock(
+        &self,
+        _: &str,
+        _: &crate::database::LockOptions,
+    ) -> crate::Result<Self::Lock> {
+        Ok(Lock {})
+    }
+
+    fn stats(&self) -> crate::database::PoolStats {
+        Default::default()
+    }
+}
+
+pub struct Lock {}
+
+impl crate::database::Lock for Lock {
+    fn is_held(&self) -> bool {
+        true
+    }
+
+    async fn release(self) -> crate::Result<()> {
+        Ok(())
+    }
 }
 
 impl<'a> crate::database::Handle for Handle<'a> {
@@ -1,8 +1,14 @@
+pub mod instrument;
 pub mod mock;
+pub mod mysql;
 pub mod postgres;
 pub mod sqlite;
 
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
 use anyhow::anyhow;
+use futures_core::{future::BoxFuture, stream::BoxStream};
 
 pub fn into_error(err: sqlx::Error) -> crate::Error {
     match err {
@@ -12,12 +18,240 @@ pub fn into_error(err: sqlx::Error) -> crate::Error {
             crate::Error::Unhandled(anyhow!("sqlx io error: {:?}", io_err))
         }
         sqlx::Error::Database(ref db_err) => match db_err.code().as_deref() {
-            Some("23505") => crate::Error::AlreadyExists("".into()),
+            Some("23505") | Some("1555") | Some("2067") => {
+                crate::Error::AlreadyExists("".into())
+            }
             Some("23000") if db_err.message().contains("Duplicate entry") => {
                 crate::Error::AlreadyExists("".into())
             }
-            _ => crate::Error::Database(anyhow!("database error: {:?}", err)),
+            // keeps the sqlx error for `Error::is_retryable`
+            _ => crate::Error::Database(
+                anyhow::Error::new(err).context("database error"),
+            ),
         },
         _ => crate::Error::Database(anyhow!("database error: {:?}", err)),
     }
 }
+
+/// Spawns the renewal loop of a lock, calling `renew` every `interval`
+/// until it reports the lock is gone or fails, then clears `held`.
+pub(crate) fn keep_alive<F, Fut>(
+    name: String,
+    interval: std::time::Duration,
+    held: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    mut renew: F,
+) -> tokio::task::JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = crate::Result<bool>> + Send,
+{
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            match renew().await {
+                Ok(true) => continue,
+                Ok(false) => tracing::warn!("lock {} was lost", name),
+                Err(e) => {
+                    tracing::warn!("lock {} keep-alive failed: {:?}", name, e)
+                }
+            }
+            held.store(false, std::sync::atomic::Ordering::Release);
+            return;
+        }
+    })
+}
+
+/// Shared body of the backends' `Database::transaction`.
+pub(crate) async fn transaction<'a, D, T, F>(
+    database: &'a D,
+    retry: &crate::config::TransactionRetryConfig,
+    mut f: F,
+) -> crate::Result<T>
+where
+    D: crate::database::Database,
+    T: Send,
+    F: for<'h> FnMut(
+            crate::database::Tx<'h, 'a, D::Handle<'a>>,
+        )
+            -> futures_core::future::BoxFuture<'h, crate::Result<T>>
+        + Send,
+{
+    use crate::database::Handle;
+
+    let mut attempt = 0;
+    loop {
+        // a panic in `f` drops the handle, which rolls the transaction back
+        let mut handle = database.tx_handle().await?;
+        let result = match f(crate::database::Tx::new(&mut handle)).await {
+            Ok(value) => handle.commit().await.map(|_| value),
+            Err(e) => {
+                if let Err(rollback) = handle.rollback().await {
+                    tracing::warn!(
+                        "transaction rollback failed: {:?}",
+                        rollback
+                    );
+                }
+                Err(e)
+            }
+        };
+        match result {
+            Err(e) if e.is_retryable() && attempt < retry.max_retries => {
+                let backoff = retry.backoff(attempt);
+                tracing::debug!(
+                    "transaction retry {} in {:?}: {}",
+                    attempt + 1,
+                    backoff,
+                    e
+                );
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Pool options from `database.pool_size` and `database.pool`, the
+/// statement cache being set on each backend's connect options.
+pub(crate) fn pool_options<DB: sqlx::Database>(
+    config: &crate::config::DatabaseConfig,
+) -> sqlx::pool::PoolOptions<DB> {
+    sqlx::pool::PoolOptions::<DB>::new()
+        .max_connections(config.pool_size)
+        .min_connections(config.pool.min_connections)
+        .acquire_timeout(Duration::from_millis(config.pool.acquire_timeout_ms))
+        .idle_timeout(config.pool.idle_timeout_ms.map(Duration::from_millis))
+        .max_lifetime(config.pool.max_lifetime_ms.map(Duration::from_millis))
+}
+
+type Fetched<DB> = sqlx::Either<
+    <DB as sqlx::Database>::QueryResult,
+    <DB as sqlx::Database>::Row,
+>;
+
+/// Acquire counters of a pool, shared by its handles. Pool handles take
+/// their connection through [`PoolMetrics::acquire`] instead of leaving it
+/// to sqlx, so every wait is measured.
+#[derive(Debug, Default)]
+pub struct PoolMetrics {
+    acquired: AtomicU64,
+    timeouts: AtomicU64,
+    wait_total_us: AtomicU64,
+    wait_max_us: AtomicU64,
+}
+
+impl PoolMetrics {
+    pub async fn acquire<DB: sqlx::Database>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> Result<sqlx::pool::PoolConnection<DB>, sqlx::Error> {
+        let start = Instant::now();
+        let result = pool.acquire().await;
+        let wait = start.elapsed().as_micros() as u64;
+        match &result {
+            Ok(_) => {
+                self.acquired.fetch_add(1, Ordering::Relaxed);
+                self.wait_total_us.fetch_add(wait, Ordering::Relaxed);
+                self.wait_max_us.fetch_max(wait, Ordering::Relaxed);
+            }
+            Err(sqlx::Error::PoolTimedOut) => {
+                self.timeouts.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    pub async fn begin<DB: sqlx::Database>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> Result<sqlx::Transaction<'static, DB>, sqlx::Error> {
+        sqlx::Transaction::begin(self.acquire(pool).await?, None).await
+    }
+
+    pub fn stats<DB: sqlx::Database>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> crate::database::PoolStats {
+        let size = pool.size();
+        let idle = pool.num_idle() as u32;
+        crate::database::PoolStats {
+            max_connections: pool.options().get_max_connections(),
+            size,
+            idle,
+            in_use: size.saturating_sub(idle),
+            acquired: self.acquired.load(Ordering::Relaxed),
+            acquire_timeouts: self.timeouts.load(Ordering::Relaxed),
+            acquire_wait_total: Duration::from_micros(
+                self.wait_total_us.load(Ordering::Relaxed),
+            ),
+            acquire_wait_max: Duration::from_micros(
+                self.wait_max_us.load(Ordering::Relaxed),
+            ),
+        }
+    }
+
+    /// `sqlx::Executor::fetch_many` of a pool, with a timed acquire.
+    pub(crate) fn fetch_many<'e, 'q: 'e, DB, E>(
+        self: &std::sync::Arc<Self>,
+        pool: &sqlx::Pool<DB>,
+        query: E,
+    ) -> BoxStream<'e, Result<Fetched<DB>, sqlx::Error>>
+    where
+        DB: sqlx::Database,
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+        E: 'q + sqlx::Execute<'q, DB>,
+    {
+        use futures_util::StreamExt;
+
+        let metrics = self.clone();
+        let pool = pool.clone();
+        // the rows borrow the connection, so the future owning it sends
+        // them through a channel, polled alongside the future
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+        let producer = async move {
+            use sqlx::Executor;
+
+            let mut conn = match metrics.acquire(&pool).await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+            let mut stream = (&mut *conn).fetch_many(query);
+            while let Some(item) = stream.next().await {
+                let failed = item.is_err();
+                if sender.send(item).await.is_err() || failed {
+                    return;
+                }
+            }
+        };
+        Box::pin(futures_util::stream::select(
+            futures_util::stream::poll_fn(move |cx| receiver.poll_recv(cx)),
+            futures_util::stream::once(producer)
+                .filter_map(|()| async { None }),
+        ))
+    }
+
+    /// `sqlx::Executor::fetch_optional` of a pool, with a timed acquire.
+    pub(crate) fn fetch_optional<'e, 'q: 'e, DB, E>(
+        self: &std::sync::Arc<Self>,
+        pool: &sqlx::Pool<DB>,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<DB::Row>, sqlx::Error>>
+    where
+        DB: sqlx::Database,
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+        E: 'q + sqlx::Execute<'q, DB>,
+    {
+        let metrics = self.clone();
+        let pool = pool.clone();
+        Box::pin(async move {
+            use sqlx::Executor;
+
+            let mut conn = metrics.acquire(&pool).await?;
+            (&mut *conn).fetch_optional(query).await
+        })
+    }
+}
@@ -1,28 +1,174 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use std::time::Duration;
+
 use futures_core::{future::BoxFuture, stream::BoxStream};
+use sha2::Digest;
+
+/// `db.system` of the query spans.
+const SYSTEM: &str = "postgresql";
 
 pub type DefaultDriver = sqlx::Postgres;
 
 #[derive(Debug)]
 pub enum Handle<'c> {
-    Pool(sqlx::Pool<DefaultDriver>),
-    Transaction(sqlx::Transaction<'c, DefaultDriver>),
+    // both with the slow query threshold of the database they came from
+    Pool(
+        sqlx::Pool<DefaultDriver>,
+        Arc<crate::database::internal::PoolMetrics>,
+        Option<std::time::Duration>,
+    ),
+    Transaction(
+        sqlx::Transaction<'c, DefaultDriver>,
+        Option<std::time::Duration>,
+    ),
 }
 
 #[derive(Debug, Clone)]
 pub struct Database {
     pub pool: sqlx::Pool<DefaultDriver>,
+    pub metrics: Arc<crate::database::internal::PoolMetrics>,
+    pub replicas: Arc<Replicas>,
+    pub retry: crate::config::TransactionRetryConfig,
+    pub slow_query: Option<std::time::Duration>, // `database.slow_query_ms`
 }
 
 impl Database {
+    /// `database.url` schemes this backend connects to.
+    pub const SCHEMES: &[&str] = &["postgres", "postgresql"];
+
     pub async fn new(
         config: &crate::config::DatabaseConfig,
     ) -> crate::Result<Self> {
-        let pool = sqlx::pool::PoolOptions::<DefaultDriver>::new()
-            .max_connections(config.pool_size)
-            .connect(&config.url)
+        let pool = crate::database::internal::pool_options(config)
+            .connect_with(connect_options(&config.url, config)?)
             .await
             .map_err(crate::database::internal::into_error)?;
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            metrics: Default::default(),
+            replicas: Replicas::connect(config)?,
+            retry: config.retry.clone(),
+            slow_query: config
+                .slow_query_ms
+                .map(std::time::Duration::from_millis),
+        })
+    }
+}
+
+fn connect_options(
+    url: &str,
+    config: &crate::config::DatabaseConfig,
+) -> crate::Result<sqlx::postgres::PgConnectOptions> {
+    Ok(url
+        .parse::<sqlx::postgres::PgConnectOptions>()
+        .map_err(crate::database::internal::into_error)?
+        .statement_cache_capacity(config.pool.statement_cache_size))
+}
+
+/// Read-only pools behind `read_handle`. They connect lazily so a replica
+/// being down doesn't keep the app from starting, and only serve reads
+/// once a health check reached them.
+#[derive(Debug, Default)]
+pub struct Replicas {
+    replicas: Vec<Replica>,
+    next: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct Replica {
+    pool: sqlx::Pool<DefaultDriver>,
+    metrics: Arc<crate::database::internal::PoolMetrics>,
+    healthy: AtomicBool,
+}
+
+impl Replicas {
+    const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+    const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn connect(
+        config: &crate::config::DatabaseConfig,
+    ) -> crate::Result<Arc<Self>> {
+        let replicas = config
+            .replicas
+            .iter()
+            .map(|url| {
+                let pool = crate::database::internal::pool_options(config)
+                    .connect_lazy_with(connect_options(url, config)?);
+                Ok(Replica {
+                    pool,
+                    metrics: Default::default(),
+                    healthy: AtomicBool::new(false),
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        let replicas = Arc::new(Self {
+            replicas,
+            next: AtomicUsize::new(0),
+        });
+        if !replicas.replicas.is_empty() {
+            Self::spawn_health_check(&replicas);
+        }
+        Ok(replicas)
+    }
+
+    /// Checks every replica right away, then every `CHECK_INTERVAL` until
+    /// the last `Database` sharing them is dropped.
+    fn spawn_health_check(replicas: &Arc<Self>) {
+        let replicas = Arc::downgrade(replicas);
+        tokio::spawn(async move {
+            while let Some(replicas) = replicas.upgrade() {
+                replicas.check().await;
+                drop(replicas);
+                tokio::time::sleep(Self::CHECK_INTERVAL).await;
+            }
+        });
+    }
+
+    async fn check(&self) {
+        for replica in &self.replicas {
+            let healthy = tokio::time::timeout(
+                Self::CHECK_TIMEOUT,
+                sqlx::query("SELECT 1").execute(&replica.pool),
+            )
+            .await
+            .is_ok_and(|result| result.is_ok());
+            if replica.healthy.swap(healthy, Ordering::AcqRel) != healthy {
+                let host =
+                    replica.pool.connect_options().get_host().to_string();
+                if healthy {
+                    tracing::info!("replica {} is healthy", host);
+                } else {
+                    tracing::warn!("replica {} is unhealthy", host);
+                }
+            }
+        }
+    }
+
+    /// Round-robin over the healthy replicas.
+    fn pick(&self) -> Option<&Replica> {
+        let len = self.replicas.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..len)
+            .map(|i| &self.replicas[(start + i) % len])
+            .find(|replica| replica.healthy.load(Ordering::Acquire))
+    }
+
+    pub fn healthy(&self) -> usize {
+        self.replicas
+            .iter()
+            .filter(|replica| replica.healthy.load(Ordering::Acquire))
+            .count()
+    }
+
+    /// Pool snapshots, in the order of `database.replicas`.
+    pub fn stats(&self) -> Vec<crate::database::PoolStats> {
+        self.replicas
+            .iter()
+            .map(|replica| replica.metrics.stats(&replica.pool))
+            .collect()
     }
 }
 
@@ -31,21 +177,170 @@ impl crate::database::Database for Database {
         = Handle<'h>
     where
         Self: 'h;
+    type Lock = Lock;
 
     fn handle(&self) -> Self::Handle<'_> {
-        Handle::Pool(self.pool.clone())
+        Handle::Pool(self.pool.clone(), self.metrics.clone(), self.slow_query)
+    }
+
+    fn read_handle(&self) -> Self::Handle<'_> {
+        match self.replicas.pick() {
+            Some(replica) => Handle::Pool(
+                replica.pool.clone(),
+                replica.metrics.clone(),
+                self.slow_query,
+            ),
+            None => Handle::Pool(
+                self.pool.clone(),
+                self.metrics.clone(),
+                self.slow_query,
+            ),
+        }
     }
 
     async fn tx_handle(&self) -> crate::Result<Self::Handle<'_>> {
         let tx = self
-            .pool
-            .begin()
+            .metrics
+            .begin(&self.pool)
             .await
             .map_err(crate::database::internal::into_error)?;
-        Ok(Handle::Transaction(tx))
+        Ok(Handle::Transaction(tx, self.slow_query))
+    }
+
+    async fn transaction<'a, T, F>(&'a self, f: F) -> crate::Result<T>
+    where
+        T: Send,
+        F: for<'h> FnMut(
+                crate::database::Tx<'h, 'a, Self::Handle<'a>>,
+            ) -> BoxFuture<'h, crate::Result<T>>
+            + Send,
+    {
+        crate::database::internal::transaction(self, &self.retry, f).await
+    }
+
+    async fn savepoint<'s>(
+        &'s self,
+        handle: &'s mut Self::Handle<'_>,
+    ) -> crate::Result<Self::Handle<'s>> {
+        handle.savepoint().await
+    }
+
+    async fn lock(
+        &self,
+        name: &str,
+        options: &crate::database::LockOptions,
+    ) -> crate::Result<Self::Lock> {
+        Lock::acquire(&self.pool, name, options).await
+    }
+
+    fn stats(&self) -> crate::database::PoolStats {
+        self.metrics.stats(&self.pool)
     }
 }
 
+/// Session level advisory lock. It is taken on a connection of its own,
+/// opened outside the pool so holding it never starves a small pool, and
+/// the server releases it when that connection goes away.
+#[derive(Debug)]
+pub struct Lock {
+    key: i64,
+    conn: Arc<tokio::sync::Mutex<sqlx::PgConnection>>,
+    held: Arc<AtomicBool>,
+    keep_alive: tokio::task::JoinHandle<()>,
+}
+
+impl Lock {
+    async fn acquire(
+        pool: &sqlx::Pool<DefaultDriver>,
+        name: &str,
+        options: &crate::database::LockOptions,
+    ) -> crate::Result<Self> {
+        use sqlx::Connection;
+
+        let key = lock_key(name);
+        let deadline = tokio::time::Instant::now() + options.timeout;
+        let mut conn =
+            sqlx::PgConnection::connect_with(&pool.connect_options())
+                .await
+                .map_err(crate::database::internal::into_error)?;
+        loop {
+            let (acquired,): (bool,) =
+                sqlx::query_as("SELECT pg_try_advisory_lock($1)")
+                    .bind(key)
+                    .fetch_one(&mut conn)
+                    .await
+                    .map_err(crate::database::internal::into_error)?;
+            if acquired {
+                break;
+            }
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                let _ = conn.close().await;
+                return Err(crate::Error::Timeout);
+            }
+            tokio::time::sleep(options.retry_interval.min(deadline - now))
+                .await;
+        }
+
+        // pings keep the session from being closed as idle, which would
+        // release the lock
+        let conn = Arc::new(tokio::sync::Mutex::new(conn));
+        let held = Arc::new(AtomicBool::new(true));
+        let keep_alive = crate::database::internal::keep_alive(
+            name.to_string(),
+            options.keep_alive_interval(),
+            held.clone(),
+            {
+                let conn = conn.clone();
+                move || {
+                    let conn = conn.clone();
+                    async move {
+                        sqlx::query("SELECT 1")
+                            .execute(&mut *conn.lock().await)
+                            .await
+                            .map_err(crate::database::internal::into_error)?;
+                        Ok(true)
+                    }
+                }
+            },
+        );
+        Ok(Self {
+            key,
+            conn,
+            held,
+            keep_alive,
+        })
+    }
+}
+
+impl crate::database::Lock for Lock {
+    fn is_held(&self) -> bool {
+        self.held.load(Ordering::Acquire)
+    }
+
+    async fn release(self) -> crate::Result<()> {
+        self.keep_alive.abort();
+        sqlx::query("SELECT pg_advisory_unlock($1)")
+            .bind(self.key)
+            .execute(&mut *self.conn.lock().await)
+            .await
+            .map_err(crate::database::internal::into_error)?;
+        Ok(())
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        self.keep_alive.abort();
+    }
+}
+
+/// Advisory locks are keyed by a bigint, taken from the name's digest.
+fn lock_key(name: &str) -> i64 {
+    let digest = sha2::Sha256::digest(name.as_bytes());
+    i64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
 #[derive(Debug)]
 pub struct Executor<'h, 'c> {
     pub handle: &'h mut Handle<'c>,
@@ -55,12 +350,35 @@ impl<'c> Handle<'c> {
     pub fn executor(&mut self) -> Executor<'_, 'c> {
         Executor { handle: self }
     }
+
+    fn slow_query(&self) -> Option<std::time::Duration> {
+        match self {
+            Handle::Pool(_, _, slow_query)
+            | Handle::Transaction(_, slow_query) => *slow_query,
+        }
+    }
+
+    /// Nested handle that commits with `RELEASE SAVEPOINT` and rolls back
+    /// with `ROLLBACK TO SAVEPOINT`, leaving this transaction open either
+    /// way. Dropping it uncommitted rolls it back. On a pool handle it is
+    /// a transaction of its own.
+    pub async fn savepoint(&mut self) -> crate::Result<Handle<'_>> {
+        use sqlx::Connection;
+
+        let slow_query = self.slow_query();
+        let tx = match self {
+            Handle::Pool(pool, metrics, _) => metrics.begin(pool).await,
+            Handle::Transaction(tx, _) => tx.begin().await,
+        }
+        .map_err(crate::database::internal::into_error)?;
+        Ok(Handle::Transaction(tx, slow_query))
+    }
 }
 
 impl<'c> crate::database::Handle for Handle<'c> {
     async fn commit(self) -> Result<(), crate::Error> {
         match self {
-            Handle::Transaction(tx) => {
+            Handle::Transaction(tx, _) => {
                 tx.commit().await.map_err(crate::database::internal::into_error)
             }
             _ => Ok(()),
@@ -69,7 +387,7 @@ impl<'c> crate::database::Handle for Handle<'c> {
 
     async fn rollback(self) -> Result<(), crate::Error> {
         match self {
-            Handle::Transaction(tx) => tx
+            Handle::Transaction(tx, _) => tx
                 .rollback()
                 .await
                 .map_err(crate::database::internal::into_error),
@@ -98,10 +416,19 @@ impl<'h, 'c> sqlx::Executor<'h> for Executor<'h, 'c> {
         'h: 'e,
         E: 'q + sqlx::Execute<'q, Self::Database>,
     {
-        match self.handle {
-            Handle::Pool(pool) => pool.fetch_many(query),
-            Handle::Transaction(tx) => tx.fetch_many(query),
-        }
+        let sql = query.sql();
+        let slow_query = self.handle.slow_query();
+        let stream = match self.handle {
+            Handle::Pool(pool, metrics, _) => metrics.fetch_many(pool, query),
+            Handle::Transaction(tx, _) => tx.fetch_many(query),
+        };
+        super::instrument::fetch_many::<DefaultDriver>(
+            SYSTEM,
+            sql,
+            slow_query,
+            stream,
+            |result| result.rows_affected(),
+        )
     }
 
     fn fetch_optional<'e, 'q: 'e, E>(
@@ -115,10 +442,17 @@ impl<'h, 'c> sqlx::Executor<'h> for Executor<'h, 'c> {
         'h: 'e,
         E: 'q + sqlx::Execute<'q, Self::Database>,
     {
-        match self.handle {
-            Handle::Pool(pool) => pool.fetch_optional(query),
-            Handle::Transaction(tx) => tx.fetch_optional(query),
-        }
+        let sql = query.sql();
+        let slow_query = self.handle.slow_query();
+        let future = match self.handle {
+            Handle::Pool(pool, metrics, _) => {
+                metrics.fetch_optional(pool, query)
+            }
+            Handle::Transaction(tx, _) => tx.fetch_optional(query),
+        };
+        super::instrument::fetch_optional::<DefaultDriver>(
+            SYSTEM, sql, slow_query, future,
+        )
     }
 
     fn prepare_with<'e, 'q: 'e>(
@@ -133,8 +467,8 @@ impl<'h, 'c> sqlx::Executor<'h> for Executor<'h, 'c> {
         'h: 'e,
     {
         match self.handle {
-            Handle::Pool(pool) => pool.prepare_with(sql, parameters),
-            Handle::Transaction(tx) => tx.prepare_with(sql, parameters),
+            Handle::Pool(pool, ..) => pool.prepare_with(sql, parameters),
+            Handle::Transaction(tx, _) => tx.prepare_with(sql, parameters),
         }
     }
 
@@ -146,8 +480,8 @@ impl<'h, 'c> sqlx::Executor<'h> for Executor<'h, 'c> {
         'h: 'e,
     {
         match self.handle {
-            Handle::Pool(pool) => pool.describe(sql),
-            Handle::Transaction(tx) => tx.describe(sql),
+            Handle::Pool(pool, ..) => pool.describe(sql),
+            Handle::Transaction(tx, _) => tx.describe(sql),
         }
     }
 }
@@ -230,4 +564,87 @@ mod tests {
         assert_eq!(result, 3);
         ctx.commit().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_lock() {
+        use crate::database::Lock;
+
+        let db = db_connect().await.unwrap();
+        let options = crate::database::LockOptions {
+            timeout: std::time::Duration::from_millis(100),
+            ..Default::default()
+        };
+
+        let lock = db.lock("job", &options).await.unwrap();
+        assert!(lock.is_held());
+        assert!(matches!(
+            db.lock("job", &options).await,
+            Err(crate::Error::Timeout)
+        ));
+        // the pool stays usable while the lock is held
+        assert_eq!(accept_handle(&mut db.handle()).await.unwrap(), 3);
+        lock.release().await.unwrap();
+
+        // closing the lock's session releases it
+        drop(db.lock("job", &options).await.unwrap());
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        db.lock("job", &options).await.unwrap().release().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_savepoint() {
+        let db = db_connect().await.unwrap();
+        sqlx::query("CREATE TABLE IF NOT EXISTS savepoint_test (id INTEGER)")
+            .execute(db.handle().executor())
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM savepoint_test")
+            .execute(db.handle().executor())
+            .await
+            .unwrap();
+        async fn insert(handle: &mut super::Handle<'_>, id: i32) {
+            sqlx::query("INSERT INTO savepoint_test (id) VALUES ($1)")
+                .bind(id)
+                .execute(handle.executor())
+                .await
+                .unwrap();
+        }
+
+        let mut tx = db.tx_handle().await.unwrap();
+        insert(&mut tx, 1).await;
+        let mut savepoint = tx.savepoint().await.unwrap();
+        insert(&mut savepoint, 2).await;
+        savepoint.rollback().await.unwrap();
+
+        let mut savepoint = db.savepoint(&mut tx).await.unwrap();
+        insert(&mut savepoint, 3).await;
+        let mut nested = savepoint.savepoint().await.unwrap();
+        insert(&mut nested, 4).await;
+        drop(nested);
+        savepoint.commit().await.unwrap();
+        tx.commit().await.unwrap();
+
+        let rows: Vec<(i32,)> =
+            sqlx::query_as("SELECT id FROM savepoint_test ORDER BY id")
+                .fetch_all(db.handle().executor())
+                .await
+                .unwrap();
+        assert_eq!(rows, vec![(1,), (3,)]);
+    }
+
+    #[tokio::test]
+    async fn test_read_handle() {
+        let mut config = crate::config::Config::test_config().database;
+        config.replicas =
+            vec![config.url.clone(), "postgres://localhost:1/none".into()];
+        let db = super::Database::new(&config).await.unwrap();
+
+        // reads fall back to the primary until a replica is checked
+        assert_eq!(accept_handle(&mut db.read_handle()).await.unwrap(), 3);
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert_eq!(db.replicas.healthy(), 1);
+        for _ in 0..4 {
+            assert_eq!(accept_handle(&mut db.read_handle()).await.unwrap(), 3);
+        }
+    }
 }
@@ -1,47 +1,238 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
 use futures_core::{future::BoxFuture, stream::BoxStream};
 
+/// `db.system` of the query spans.
+const SYSTEM: &str = "sqlite";
+
 pub type DefaultDriver = sqlx::Sqlite;
 
 #[derive(Debug)]
 pub enum Handle<'c> {
-    Pool(sqlx::Pool<DefaultDriver>),
-    Transaction(sqlx::Transaction<'c, DefaultDriver>),
+    // both with the slow query threshold of the database they came from
+    Pool(
+        sqlx::Pool<DefaultDriver>,
+        Arc<crate::database::internal::PoolMetrics>,
+        Option<std::time::Duration>,
+    ),
+    Transaction(
+        sqlx::Transaction<'c, DefaultDriver>,
+        Option<std::time::Duration>,
+    ),
 }
 
+#[derive(Debug, Clone)]
 pub struct Database {
     pub pool: sqlx::Pool<DefaultDriver>,
+    pub metrics: Arc<crate::database::internal::PoolMetrics>,
+    pub retry: crate::config::TransactionRetryConfig,
+    pub slow_query: Option<std::time::Duration>, // `database.slow_query_ms`
 }
 
 impl Database {
+    /// `database.url` schemes this backend connects to.
+    pub const SCHEMES: &[&str] = &["sqlite"];
+
     pub async fn new(
         config: &crate::config::DatabaseConfig,
     ) -> crate::Result<Self> {
-        let pool = sqlx::pool::PoolOptions::<DefaultDriver>::new()
-            .max_connections(config.pool_size)
-            .connect(&config.url)
+        let pool = crate::database::internal::pool_options(config)
+            .connect_with(connect_options(&config.url, config)?)
             .await
             .map_err(crate::database::internal::into_error)?;
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            metrics: Default::default(),
+            retry: config.retry.clone(),
+            slow_query: config
+                .slow_query_ms
+                .map(std::time::Duration::from_millis),
+        })
     }
 }
 
+fn connect_options(
+    url: &str,
+    config: &crate::config::DatabaseConfig,
+) -> crate::Result<sqlx::sqlite::SqliteConnectOptions> {
+    Ok(url
+        .parse::<sqlx::sqlite::SqliteConnectOptions>()
+        .map_err(crate::database::internal::into_error)?
+        .statement_cache_capacity(config.pool.statement_cache_size))
+}
+
 impl crate::database::Database for Database {
     type Handle<'h>
         = Handle<'h>
     where
         Self: 'h;
+    type Lock = Lock;
 
     fn handle(&self) -> Self::Handle<'_> {
-        Handle::Pool(self.pool.clone())
+        Handle::Pool(self.pool.clone(), self.metrics.clone(), self.slow_query)
     }
 
     async fn tx_handle(&self) -> crate::Result<Self::Handle<'_>> {
         let tx = self
-            .pool
-            .begin()
+            .metrics
+            .begin(&self.pool)
             .await
             .map_err(crate::database::internal::into_error)?;
-        Ok(Handle::Transaction(tx))
+        Ok(Handle::Transaction(tx, self.slow_query))
+    }
+
+    async fn transaction<'a, T, F>(&'a self, f: F) -> crate::Result<T>
+    where
+        T: Send,
+        F: for<'h> FnMut(
+                crate::database::Tx<'h, 'a, Self::Handle<'a>>,
+            ) -> BoxFuture<'h, crate::Result<T>>
+            + Send,
+    {
+        crate::database::internal::transaction(self, &self.retry, f).await
+    }
+
+    async fn savepoint<'s>(
+        &'s self,
+        handle: &'s mut Self::Handle<'_>,
+    ) -> crate::Result<Self::Handle<'s>> {
+        handle.savepoint().await
+    }
+
+    async fn lock(
+        &self,
+        name: &str,
+        options: &crate::database::LockOptions,
+    ) -> crate::Result<Self::Lock> {
+        Lock::acquire(&self.pool, name, options).await
+    }
+
+    fn stats(&self) -> crate::database::PoolStats {
+        self.metrics.stats(&self.pool)
+    }
+}
+
+/// Lease in the `stardust_lock` table, sqlite having no advisory locks. A
+/// lease whose owner stopped renewing it can be taken over once it
+/// expires.
+#[derive(Debug)]
+pub struct Lock {
+    pool: sqlx::Pool<DefaultDriver>,
+    name: String,
+    owner: String,
+    held: Arc<AtomicBool>,
+    keep_alive: tokio::task::JoinHandle<()>,
+}
+
+impl Lock {
+    async fn acquire(
+        pool: &sqlx::Pool<DefaultDriver>,
+        name: &str,
+        options: &crate::database::LockOptions,
+    ) -> crate::Result<Self> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS stardust_lock (
+                name TEXT PRIMARY KEY,
+                owner TEXT NOT NULL,
+                expires_at INTEGER NOT NULL
+            )"#,
+        )
+        .execute(pool)
+        .await
+        .map_err(crate::database::internal::into_error)?;
+
+        let owner = crate::utils::generate_uid();
+        let lease = options.lease.as_millis() as i64;
+        let deadline = tokio::time::Instant::now() + options.timeout;
+        loop {
+            let now = chrono::Utc::now().timestamp_millis();
+            let result = sqlx::query(
+                r#"INSERT INTO stardust_lock (name, owner, expires_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (name) DO UPDATE
+                SET owner = excluded.owner, expires_at = excluded.expires_at
+                WHERE stardust_lock.expires_at < $4"#,
+            )
+            .bind(name)
+            .bind(&owner)
+            .bind(now + lease)
+            .bind(now)
+            .execute(pool)
+            .await
+            .map_err(crate::database::internal::into_error)?;
+            if result.rows_affected() == 1 {
+                break;
+            }
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                return Err(crate::Error::Timeout);
+            }
+            tokio::time::sleep(options.retry_interval.min(deadline - now))
+                .await;
+        }
+
+        let held = Arc::new(AtomicBool::new(true));
+        let keep_alive = crate::database::internal::keep_alive(
+            name.to_string(),
+            options.keep_alive_interval(),
+            held.clone(),
+            {
+                let pool = pool.clone();
+                let name = name.to_string();
+                let owner = owner.clone();
+                move || {
+                    let pool = pool.clone();
+                    let name = name.clone();
+                    let owner = owner.clone();
+                    async move {
+                        let result = sqlx::query(
+                            r#"UPDATE stardust_lock SET expires_at = $1
+                            WHERE name = $2 AND owner = $3"#,
+                        )
+                        .bind(chrono::Utc::now().timestamp_millis() + lease)
+                        .bind(name)
+                        .bind(owner)
+                        .execute(&pool)
+                        .await
+                        .map_err(crate::database::internal::into_error)?;
+                        Ok(result.rows_affected() == 1)
+                    }
+                }
+            },
+        );
+        Ok(Self {
+            pool: pool.clone(),
+            name: name.to_string(),
+            owner,
+            held,
+            keep_alive,
+        })
+    }
+}
+
+impl crate::database::Lock for Lock {
+    fn is_held(&self) -> bool {
+        self.held.load(Ordering::Acquire)
+    }
+
+    async fn release(self) -> crate::Result<()> {
+        self.keep_alive.abort();
+        sqlx::query("DELETE FROM stardust_lock WHERE name = $1 AND owner = $2")
+            .bind(&self.name)
+            .bind(&self.owner)
+            .execute(&self.pool)
+            .await
+            .map_err(crate::database::internal::into_error)?;
+        Ok(())
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        self.keep_alive.abort();
     }
 }
 
@@ -54,12 +245,35 @@ impl<'c> Handle<'c> {
     pub fn executor(&mut self) -> Executor<'_, 'c> {
         Executor { handle: self }
     }
+
+    fn slow_query(&self) -> Option<std::time::Duration> {
+        match self {
+            Handle::Pool(_, _, slow_query)
+            | Handle::Transaction(_, slow_query) => *slow_query,
+        }
+    }
+
+    /// Nested handle that commits with `RELEASE SAVEPOINT` and rolls back
+    /// with `ROLLBACK TO SAVEPOINT`, leaving this transaction open either
+    /// way. Dropping it uncommitted rolls it back. On a pool handle it is
+    /// a transaction of its own.
+    pub async fn savepoint(&mut self) -> crate::Result<Handle<'_>> {
+        use sqlx::Connection;
+
+        let slow_query = self.slow_query();
+        let tx = match self {
+            Handle::Pool(pool, metrics, _) => metrics.begin(pool).await,
+            Handle::Transaction(tx, _) => tx.begin().await,
+        }
+        .map_err(crate::database::internal::into_error)?;
+        Ok(Handle::Transaction(tx, slow_query))
+    }
 }
 
 impl<'c> crate::database::Handle for Handle<'c> {
     async fn commit(self) -> Result<(), crate::Error> {
         match self {
-            Handle::Transaction(tx) => {
+            Handle::Transaction(tx, _) => {
                 tx.commit().await.map_err(crate::database::internal::into_error)
             }
             _ => Ok(()),
@@ -68,7 +282,7 @@ impl<'c> crate::database::Handle for Handle<'c> {
 
     async fn rollback(self) -> Result<(), crate::Error> {
         match self {
-            Handle::Transaction(tx) => tx
+            Handle::Transaction(tx, _) => tx
                 .rollback()
                 .await
                 .map_err(crate::database::internal::into_error),
@@ -97,10 +311,19 @@ impl<'h, 'c> sqlx::Executor<'h> for Executor<'h, 'c> {
         'h: 'e,
         E: 'q + sqlx::Execute<'q, Self::Database>,
     {
-        match self.handle {
-            Handle::Pool(pool) => pool.fetch_many(query),
-            Handle::Transaction(tx) => tx.fetch_many(query),
-        }
+        let sql = query.sql();
+        let slow_query = self.handle.slow_query();
+        let stream = match self.handle {
+            Handle::Pool(pool, metrics, _) => metrics.fetch_many(pool, query),
+            Handle::Transaction(tx, _) => tx.fetch_many(query),
+        };
+        super::instrument::fetch_many::<DefaultDriver>(
+            SYSTEM,
+            sql,
+            slow_query,
+            stream,
+            |result| result.rows_affected(),
+        )
     }
 
     fn fetch_optional<'e, 'q: 'e, E>(
@@ -114,10 +337,17 @@ impl<'h, 'c> sqlx::Executor<'h> for Executor<'h, 'c> {
         'h: 'e,
         E: 'q + sqlx::Execute<'q, Self::Database>,
     {
-        match self.handle {
-            Handle::Pool(pool) => pool.fetch_optional(query),
-            Handle::Transaction(tx) => tx.fetch_optional(query),
-        }
+        let sql = query.sql();
+        let slow_query = self.handle.slow_query();
+        let future = match self.handle {
+            Handle::Pool(pool, metrics, _) => {
+                metrics.fetch_optional(pool, query)
+            }
+            Handle::Transaction(tx, _) => tx.fetch_optional(query),
+        };
+        super::instrument::fetch_optional::<DefaultDriver>(
+            SYSTEM, sql, slow_query, future,
+        )
     }
 
     fn prepare_with<'e, 'q: 'e>(
@@ -132,8 +362,8 @@ impl<'h, 'c> sqlx::Executor<'h> for Executor<'h, 'c> {
         'h: 'e,
     {
         match self.handle {
-            Handle::Pool(pool) => pool.prepare_with(sql, parameters),
-            Handle::Transaction(tx) => tx.prepare_with(sql, parameters),
+            Handle::Pool(pool, ..) => pool.prepare_with(sql, parameters),
+            Handle::Transaction(tx, _) => tx.prepare_with(sql, parameters),
         }
     }
 
@@ -145,8 +375,8 @@ impl<'h, 'c> sqlx::Executor<'h> for Executor<'h, 'c> {
         'h: 'e,
     {
         match self.handle {
-            Handle::Pool(pool) => pool.describe(sql),
-            Handle::Transaction(tx) => tx.describe(sql),
+            Handle::Pool(pool, ..) => pool.describe(sql),
+            Handle::Transaction(tx, _) => tx.describe(sql),
         }
     }
 }
@@ -175,6 +405,10 @@ mod tests {
         let config = DatabaseConfig {
             url: "sqlite::memory:".into(),
             pool_size: 1,
+            pool: Default::default(),
+            replicas: vec![],
+            slow_query_ms: None,
+            retry: Default::default(),
         };
         Ok(super::Database::new(&config).await?)
     }
@@ -234,4 +468,273 @@ mod tests {
         assert_eq!(result, 3);
         ctx.commit().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_lock() {
+        use crate::database::Lock;
+
+        let db = crate::testing::database().await.unwrap();
+        let options = crate::database::LockOptions {
+            timeout: std::time::Duration::from_millis(100),
+            lease: std::time::Duration::from_millis(300),
+            retry_interval: std::time::Duration::from_millis(10),
+        };
+
+        let lock = db.lock("job", &options).await.unwrap();
+        assert!(lock.is_held());
+        assert!(matches!(
+            db.lock("job", &options).await,
+            Err(crate::Error::Timeout)
+        ));
+        // names are independent
+        db.lock("other", &options).await.unwrap().release().await.unwrap();
+
+        // renewed past its lease while held
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        assert!(lock.is_held());
+        assert!(db.lock("job", &options).await.is_err());
+        lock.release().await.unwrap();
+        let lock = db.lock("job", &options).await.unwrap();
+
+        // a dropped lock is taken over once its lease expires
+        drop(lock);
+        assert!(db.lock("job", &options).await.is_err());
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        db.lock("job", &options).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_savepoint() {
+        let db = crate::testing::database().await.unwrap();
+        sqlx::query("CREATE TABLE IF NOT EXISTS savepoint_test (id INTEGER)")
+            .execute(db.handle().executor())
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM savepoint_test")
+            .execute(db.handle().executor())
+            .await
+            .unwrap();
+        async fn insert(handle: &mut super::Handle<'_>, id: i32) {
+            sqlx::query("INSERT INTO savepoint_test (id) VALUES ($1)")
+                .bind(id)
+                .execute(handle.executor())
+                .await
+                .unwrap();
+        }
+
+        let mut tx = db.tx_handle().await.unwrap();
+        insert(&mut tx, 1).await;
+        let mut savepoint = tx.savepoint().await.unwrap();
+        insert(&mut savepoint, 2).await;
+        savepoint.rollback().await.unwrap();
+
+        let mut savepoint = db.savepoint(&mut tx).await.unwrap();
+        insert(&mut savepoint, 3).await;
+        let mut nested = savepoint.savepoint().await.unwrap();
+        insert(&mut nested, 4).await;
+        drop(nested);
+        savepoint.commit().await.unwrap();
+        tx.commit().await.unwrap();
+
+        let rows: Vec<(i32,)> =
+            sqlx::query_as("SELECT id FROM savepoint_test ORDER BY id")
+                .fetch_all(db.handle().executor())
+                .await
+                .unwrap();
+        assert_eq!(rows, vec![(1,), (3,)]);
+    }
+
+    #[derive(Debug)]
+    struct SerializationFailure;
+
+    impl std::fmt::Display for SerializationFailure {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("could not serialize access")
+        }
+    }
+
+    impl std::error::Error for SerializationFailure {}
+
+    impl sqlx::error::DatabaseError for SerializationFailure {
+        fn message(&self) -> &str {
+            "could not serialize access"
+        }
+
+        fn code(&self) -> Option<std::borrow::Cow<'_, str>> {
+            Some("40001".into())
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(
+            &mut self,
+        ) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(
+            self: Box<Self>,
+        ) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> sqlx::error::ErrorKind {
+            sqlx::error::ErrorKind::Other
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction() {
+        let db = crate::testing::database().await.unwrap();
+        sqlx::query("CREATE TABLE transaction_test (id INTEGER)")
+            .execute(db.handle().executor())
+            .await
+            .unwrap();
+        async fn insert(handle: &mut super::Handle<'_>, id: i32) {
+            sqlx::query("INSERT INTO transaction_test (id) VALUES ($1)")
+                .bind(id)
+                .execute(handle.executor())
+                .await
+                .unwrap();
+        }
+
+        let id = 1;
+        let id = &id;
+        let inserted = db
+            .transaction(|mut handle| {
+                Box::pin(async move {
+                    insert(&mut handle, *id).await;
+                    Ok(*id)
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(inserted, 1);
+
+        let result: crate::Result<()> = db
+            .transaction(|mut handle| {
+                Box::pin(async move {
+                    insert(&mut handle, 2).await;
+                    Err(crate::Error::IllegalState("rejected".into()))
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(crate::Error::IllegalState(_))));
+
+        // serialization failures are retried in a new transaction
+        let mut attempts = 0;
+        db.transaction(|mut handle| {
+            attempts += 1;
+            let attempt = attempts;
+            Box::pin(async move {
+                insert(&mut handle, 10 + attempt).await;
+                if attempt < 3 {
+                    return Err(crate::database::internal::into_error(
+                        sqlx::Error::Database(Box::new(SerializationFailure)),
+                    ));
+                }
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let result: crate::Result<()> = db
+            .transaction(|_| {
+                attempts += 1;
+                Box::pin(async move {
+                    Err(crate::database::internal::into_error(
+                        sqlx::Error::Database(Box::new(SerializationFailure)),
+                    ))
+                })
+            })
+            .await;
+        assert!(result.is_err_and(|e| e.is_retryable()));
+        assert_eq!(attempts, db.retry.max_retries + 1);
+
+        // a panic drops the transaction, rolling it back
+        let panicked = tokio::spawn({
+            let db = db.clone();
+            async move {
+                let result: crate::Result<()> = db
+                    .transaction(|mut handle| {
+                        Box::pin(async move {
+                            insert(&mut handle, 3).await;
+                            panic!("boom");
+                        })
+                    })
+                    .await;
+                result
+            }
+        })
+        .await;
+        assert!(panicked.is_err());
+
+        let rows: Vec<(i32,)> =
+            sqlx::query_as("SELECT id FROM transaction_test ORDER BY id")
+                .fetch_all(db.handle().executor())
+                .await
+                .unwrap();
+        assert_eq!(rows, vec![(1,), (13,)]);
+    }
+
+    /// Dropped connections go back to the pool in a spawned task.
+    async fn released(db: &super::Database) -> crate::database::PoolStats {
+        for _ in 0..100 {
+            let stats = db.stats();
+            if stats.in_use == 0 {
+                return stats;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        db.stats()
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let db = super::Database::new(&DatabaseConfig {
+            url: "sqlite::memory:".into(),
+            pool_size: 1,
+            pool: crate::config::DatabasePoolConfig {
+                acquire_timeout_ms: 100,
+                ..Default::default()
+            },
+            replicas: vec![],
+            slow_query_ms: None,
+            retry: Default::default(),
+        })
+        .await
+        .unwrap();
+
+        let rows: Vec<(i32,)> = sqlx::query_as("SELECT 1 UNION SELECT 2")
+            .fetch_all(db.handle().executor())
+            .await
+            .unwrap();
+        assert_eq!(rows, vec![(1,), (2,)]);
+        let stats = released(&db).await;
+        assert_eq!(stats.max_connections, 1);
+        assert_eq!((stats.size, stats.idle, stats.in_use), (1, 1, 0));
+        assert_eq!(stats.acquired, 1);
+        assert!(!stats.is_saturated());
+
+        // the transaction holds the only connection
+        let tx = db.tx_handle().await.unwrap();
+        let stats = db.stats();
+        assert_eq!((stats.in_use, stats.acquired), (1, 2));
+        assert!(stats.is_saturated());
+        let result =
+            sqlx::query("SELECT 1").execute(db.handle().executor()).await;
+        assert!(matches!(result, Err(sqlx::Error::PoolTimedOut)));
+        assert_eq!(db.stats().acquire_timeouts, 1);
+        tx.rollback().await.unwrap();
+
+        let stats = released(&db).await;
+        assert_eq!(stats.in_use, 0);
+        assert!(stats.acquire_wait_max <= stats.acquire_wait_total);
+        assert!(stats.acquire_wait_avg() <= stats.acquire_wait_max);
+    }
 }
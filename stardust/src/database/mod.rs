@@ -1,5 +1,9 @@
 pub mod internal;
 
+use std::time::Duration;
+
+use futures_core::future::BoxFuture;
+
 pub trait Handle: Sync + Send {
     fn commit(
         self,
@@ -13,13 +17,197 @@ pub trait Database: Sync + Send {
     type Handle<'h>: Handle
     where
         Self: 'h;
+    type Lock: Lock;
 
     fn handle(&self) -> Self::Handle<'_>;
 
+    /// Handle for reads that tolerate replication lag, load-balanced
+    /// across the healthy replicas when the backend has any. Transactions
+    /// and their savepoints always stay on the primary.
+    fn read_handle(&self) -> Self::Handle<'_> {
+        self.handle()
+    }
+
     fn tx_handle(
         &self,
     ) -> impl std::future::Future<Output = crate::Result<Self::Handle<'_>>> + Send;
+
+    /// Runs `f` in a transaction, committed when it returns `Ok` and rolled
+    /// back on `Err` or when it panics. Serialization failures and
+    /// deadlocks rerun `f` in a new transaction with backoff, up to
+    /// `database.retry.max_retries` times, so `f` must be safe to rerun.
+    ///
+    /// ```ignore
+    /// let user = database
+    ///     .transaction(|mut handle| {
+    ///         Box::pin(async move { repo.create_user(&mut handle, user).await })
+    ///     })
+    ///     .await?;
+    /// ```
+    fn transaction<'a, T, F>(
+        &'a self,
+        f: F,
+    ) -> impl std::future::Future<Output = crate::Result<T>> + Send
+    where
+        T: Send,
+        F: for<'h> FnMut(
+                Tx<'h, 'a, Self::Handle<'a>>,
+            ) -> BoxFuture<'h, crate::Result<T>>
+            + Send;
+
+    /// Nests a handle in `handle` for generic code, see the backends'
+    /// `Handle::savepoint`.
+    fn savepoint<'s>(
+        &'s self,
+        handle: &'s mut Self::Handle<'_>,
+    ) -> impl std::future::Future<Output = crate::Result<Self::Handle<'s>>> + Send;
+
+    /// Takes the named lock shared by every process on this database,
+    /// waiting up to `options.timeout` before failing with
+    /// `Error::Timeout`. The lock is renewed in the background until it is
+    /// released or dropped.
+    fn lock(
+        &self,
+        name: &str,
+        options: &LockOptions,
+    ) -> impl std::future::Future<Output = crate::Result<Self::Lock>> + Send;
+
+    /// Snapshot of the primary pool, for health checks and metrics.
+    fn stats(&self) -> PoolStats;
+}
+
+/// Connections of a pool right now, and what acquiring them cost since it
+/// was opened. Waits are counted for every query and transaction run
+/// through a `Handle`.
+#[derive(Debug, Clone, Default)]
+pub struct PoolStats {
+    pub max_connections: u32,
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    pub acquired: u64,
+    pub acquire_timeouts: u64,
+    pub acquire_wait_total: Duration,
+    pub acquire_wait_max: Duration,
+}
+
+impl PoolStats {
+    pub fn acquire_wait_avg(&self) -> Duration {
+        match self.acquired {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos(
+                (self.acquire_wait_total.as_nanos() / n as u128) as u64,
+            ),
+        }
+    }
+
+    /// Every connection is taken, so the next acquire has to wait.
+    pub fn is_saturated(&self) -> bool {
+        self.idle == 0 && self.size >= self.max_connections
+    }
+}
+
+/// Handle lent to a [`Database::transaction`] closure. It derefs to the
+/// handle, and its `'a: 'h` bound lets the closure's future borrow locals
+/// of the caller, which a plain `&'h mut Handle<'a>` doesn't carry through
+/// a generic `Database`.
+pub struct Tx<'h, 'a, H> {
+    handle: &'h mut H,
+    _scope: std::marker::PhantomData<&'h &'a ()>,
+}
+
+impl<'h, 'a, H> Tx<'h, 'a, H> {
+    pub(crate) fn new(handle: &'h mut H) -> Self {
+        Self {
+            handle,
+            _scope: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<H> std::ops::Deref for Tx<'_, '_, H> {
+    type Target = H;
+
+    fn deref(&self) -> &H {
+        self.handle
+    }
+}
+
+impl<H> std::ops::DerefMut for Tx<'_, '_, H> {
+    fn deref_mut(&mut self) -> &mut H {
+        self.handle
+    }
+}
+
+pub trait Lock: Sync + Send {
+    /// False once the keep-alive failed to renew the lock, after which
+    /// another process may hold it.
+    fn is_held(&self) -> bool;
+
+    fn release(
+        self,
+    ) -> impl std::future::Future<Output = crate::Result<()>> + Send;
+}
+
+#[derive(Debug, Clone)]
+pub struct LockOptions {
+    /// How long to wait for a lock held by someone else.
+    pub timeout: Duration,
+    /// How long the lock survives its owner going away without releasing
+    /// it. The keep-alive runs every third of it.
+    pub lease: Duration,
+    /// Pause between attempts while the lock is taken.
+    pub retry_interval: Duration,
+}
+
+impl LockOptions {
+    pub fn keep_alive_interval(&self) -> Duration {
+        self.lease / 3
+    }
+}
+
+impl Default for LockOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            lease: Duration::from_secs(30),
+            retry_interval: Duration::from_millis(250),
+        }
+    }
 }
 
 // pub trait Context<'c>: sqlx::Executor<'c, Database = DefaultDriver> {}
 // impl<'c, T> Context<'c> for T where T: sqlx::Executor<'c, Database = DefaultDriver> {}
+
+impl crate::config::TransactionRetryConfig {
+    /// Delay before the retry following `attempt`, starting from 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff_ms
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_backoff_ms);
+        Duration::from_millis(backoff)
+    }
+}
+
+impl Default for crate::config::DatabasePoolConfig {
+    fn default() -> Self {
+        Self {
+            min_connections: 0,
+            acquire_timeout_ms: 30_000,
+            idle_timeout_ms: Some(600_000),
+            max_lifetime_ms: Some(1_800_000),
+            statement_cache_size: 100,
+        }
+    }
+}
+
+impl Default for crate::config::TransactionRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 1000,
+        }
+    }
+}
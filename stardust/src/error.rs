@@ -13,17 +13,17 @@ pub enum Error {
 
     // [client level error]
     #[error("invalid parameter: {0}")]
-    InvalidParameter(Cow<'static, str>),
+    InvalidParameter(ErrorDetail),
 
     // [business level error]
     #[error("illegal state: {0}")]
-    IllegalState(Cow<'static, str>),
+    IllegalState(ErrorDetail),
 
     #[error("already exists: {0}")]
-    AlreadyExists(Cow<'static, str>),
+    AlreadyExists(ErrorDetail),
 
     #[error("not found: {0}")]
-    NotFound(Cow<'static, str>),
+    NotFound(ErrorDetail),
 
     #[error("timeout")]
     Timeout,
@@ -37,6 +37,147 @@ pub enum Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Message of a client or business error, with an optional stable code
+/// such as `oauth2.invalid_scope` and the fields it is about. Converts
+/// from strings, so `Error::NotFound("user".into())` keeps working.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorDetail {
+    pub message: Cow<'static, str>,
+    pub code: Option<Cow<'static, str>>,
+    pub fields: Vec<FieldError>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FieldError {
+    pub field: Cow<'static, str>,
+    pub code: Cow<'static, str>, // e.g. `required`, `invalid_format`
+    pub message: Cow<'static, str>,
+}
+
+impl FieldError {
+    pub fn new(
+        field: impl Into<Cow<'static, str>>,
+        code: impl Into<Cow<'static, str>>,
+        message: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl ErrorDetail {
+    pub fn new(
+        code: impl Into<Cow<'static, str>>,
+        message: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            code: Some(code.into()),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn with_field(
+        mut self,
+        field: impl Into<Cow<'static, str>>,
+        code: impl Into<Cow<'static, str>>,
+        message: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.fields.push(FieldError::new(field, code, message));
+        self
+    }
+}
+
+impl std::fmt::Display for ErrorDetail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)?;
+        for field in &self.fields {
+            write!(f, "; {}: {}", field.field, field.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&'static str> for ErrorDetail {
+    fn from(message: &'static str) -> Self {
+        Cow::Borrowed(message).into()
+    }
+}
+
+impl From<String> for ErrorDetail {
+    fn from(message: String) -> Self {
+        Cow::<'static, str>::Owned(message).into()
+    }
+}
+
+impl From<Cow<'static, str>> for ErrorDetail {
+    fn from(message: Cow<'static, str>) -> Self {
+        Self {
+            message,
+            ..Default::default()
+        }
+    }
+}
+
+impl Error {
+    /// Stable code for clients to branch on, the detail's own code when it
+    /// has one, else one per variant. Internal errors all share
+    /// `internal`.
+    pub fn code(&self) -> &str {
+        match self {
+            Error::InvalidParameter(detail)
+            | Error::IllegalState(detail)
+            | Error::AlreadyExists(detail)
+            | Error::NotFound(detail)
+                if detail.code.is_some() =>
+            {
+                detail.code.as_deref().unwrap_or_default()
+            }
+            Error::Unhandled(_) | Error::Database(_) => "internal",
+            Error::InvalidParameter(_) => "invalid_parameter",
+            Error::IllegalState(_) => "illegal_state",
+            Error::AlreadyExists(_) => "already_exists",
+            Error::NotFound(_) => "not_found",
+            Error::Timeout => "timeout",
+            Error::Unauthorized => "unauthorized",
+            Error::Forbidden => "forbidden",
+        }
+    }
+
+    pub fn detail(&self) -> Option<&ErrorDetail> {
+        match self {
+            Error::InvalidParameter(detail)
+            | Error::IllegalState(detail)
+            | Error::AlreadyExists(detail)
+            | Error::NotFound(detail) => Some(detail),
+            _ => None,
+        }
+    }
+
+    pub fn fields(&self) -> &[FieldError] {
+        self.detail().map_or(&[], |detail| &detail.fields)
+    }
+
+    /// Serialization failures and deadlocks, which may succeed when the
+    /// transaction is run again.
+    pub fn is_retryable(&self) -> bool {
+        let Error::Database(e) = self else {
+            return false;
+        };
+        match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::Database(db_err)) => matches!(
+                db_err.code().as_deref(),
+                // sqlstate, then sqlite's SQLITE_BUSY and SQLITE_BUSY_SNAPSHOT
+                Some("40001") | Some("40P01") | Some("5") | Some("517")
+            ),
+            _ => false,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -45,7 +186,8 @@ mod tests {
     fn test_cow_borrowed() {
         let error = Error::InvalidParameter("foo".into());
         match error {
-            Error::InvalidParameter(msg) => {
+            Error::InvalidParameter(detail) => {
+                let msg = detail.message;
                 assert_eq!(msg, "foo");
                 assert!(matches!(msg, Cow::Borrowed(_)));
             }
@@ -56,9 +198,12 @@ mod tests {
     #[test]
     fn test_cow_owned() {
         let owned_string = String::from("bar");
-        let error = Error::InvalidParameter(Cow::Owned(owned_string.clone()));
+        let error = Error::InvalidParameter(
+            Cow::<str>::Owned(owned_string.clone()).into(),
+        );
         match error {
-            Error::InvalidParameter(msg) => {
+            Error::InvalidParameter(detail) => {
+                let msg = detail.message;
                 assert_eq!(msg, owned_string);
                 assert!(matches!(msg, Cow::Owned(_)));
             }
@@ -66,6 +211,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_code() {
+        assert_eq!(Error::NotFound("user".into()).code(), "not_found");
+        assert_eq!(Error::Database(anyhow::anyhow!("db")).code(), "internal");
+
+        let error = Error::InvalidParameter(
+            ErrorDetail::new("user.invalid_signup", "Invalid signup")
+                .with_field("email", "invalid_format", "Not an email address"),
+        );
+        assert_eq!(error.code(), "user.invalid_signup");
+        assert_eq!(error.fields()[0].field, "email");
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: Invalid signup; email: Not an email address"
+        );
+    }
+
     #[test]
     fn test_anyhow_context() {
         let base_error =
@@ -0,0 +1,216 @@
+//! `stardust::Error` as a `tonic::Status`. The error code and field errors
+//! travel in the standard `google.rpc.Status` details, as an `ErrorInfo`
+//! and a `BadRequest`, which grpc clients of any language can decode.
+use std::collections::HashMap;
+
+use prost::Message;
+
+const DOMAIN: &str = "stardust";
+const ERROR_INFO_TYPE: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+const BAD_REQUEST_TYPE: &str = "type.googleapis.com/google.rpc.BadRequest";
+
+/// `google.rpc.Status`
+#[derive(Clone, PartialEq, prost::Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<prost_types::Any>,
+}
+
+/// `google.rpc.ErrorInfo`
+#[derive(Clone, PartialEq, prost::Message)]
+struct ErrorInfo {
+    #[prost(string, tag = "1")]
+    reason: String,
+    #[prost(string, tag = "2")]
+    domain: String,
+    #[prost(map = "string, string", tag = "3")]
+    metadata: HashMap<String, String>,
+}
+
+/// `google.rpc.BadRequest`
+#[derive(Clone, PartialEq, prost::Message)]
+struct BadRequest {
+    #[prost(message, repeated, tag = "1")]
+    field_violations: Vec<FieldViolation>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct FieldViolation {
+    #[prost(string, tag = "1")]
+    field: String,
+    #[prost(string, tag = "2")]
+    description: String,
+    #[prost(string, tag = "3")]
+    reason: String,
+}
+
+impl From<crate::Error> for tonic::Status {
+    /// Internal errors are redacted, see `status` to expose them.
+    fn from(error: crate::Error) -> Self {
+        status(error, false)
+    }
+}
+
+/// The status of `error`. Internal errors carry their debug output as the
+/// message when `expose` is set, and "internal error" otherwise.
+pub fn status(error: crate::Error, expose: bool) -> tonic::Status {
+    use tonic::Code;
+
+    let (code, message) = match &error {
+        crate::Error::InvalidParameter(_) => (Code::InvalidArgument, None),
+        crate::Error::IllegalState(_) => (Code::FailedPrecondition, None),
+        crate::Error::AlreadyExists(_) => (Code::AlreadyExists, None),
+        crate::Error::NotFound(_) => (Code::NotFound, None),
+        crate::Error::Timeout => (Code::DeadlineExceeded, None),
+        crate::Error::Unauthorized => (Code::Unauthenticated, None),
+        crate::Error::Forbidden => (Code::PermissionDenied, None),
+        crate::Error::Unhandled(_) | crate::Error::Database(_) => {
+            tracing::error!("{:?}", error);
+            (
+                Code::Internal,
+                Some(match expose {
+                    true => format!("{:?}", error),
+                    false => "internal error".into(),
+                }),
+            )
+        }
+    };
+    let message = message.unwrap_or_else(|| match error.detail() {
+        Some(detail) => detail.message.to_string(),
+        None => error.to_string(),
+    });
+
+    let mut details = vec![prost_types::Any {
+        type_url: ERROR_INFO_TYPE.into(),
+        value: ErrorInfo {
+            reason: error.code().to_owned(),
+            domain: DOMAIN.into(),
+            metadata: HashMap::new(),
+        }
+        .encode_to_vec(),
+    }];
+    if !error.fields().is_empty() {
+        details.push(prost_types::Any {
+            type_url: BAD_REQUEST_TYPE.into(),
+            value: BadRequest {
+                field_violations: error
+                    .fields()
+                    .iter()
+                    .map(|field| FieldViolation {
+                        field: field.field.to_string(),
+                        description: field.message.to_string(),
+                        reason: field.code.to_string(),
+                    })
+                    .collect(),
+            }
+            .encode_to_vec(),
+        });
+    }
+    let status = RpcStatus {
+        code: code as i32,
+        message: message.clone(),
+        details,
+    };
+    tonic::Status::with_details(code, message, status.encode_to_vec().into())
+}
+
+/// Code and field errors of a status built from a `stardust::Error`, for
+/// grpc clients written with stardust.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorDetails {
+    pub code: Option<String>,
+    pub fields: Vec<crate::FieldError>,
+}
+
+impl ErrorDetails {
+    pub fn from_status(status: &tonic::Status) -> Self {
+        let mut details = Self::default();
+        let Ok(rpc_status) = RpcStatus::decode(status.details()) else {
+            return details;
+        };
+        for any in rpc_status.details {
+            match any.type_url.as_str() {
+                ERROR_INFO_TYPE => {
+                    if let Ok(info) = ErrorInfo::decode(any.value.as_slice()) {
+                        details.code = Some(info.reason);
+                    }
+                }
+                BAD_REQUEST_TYPE => {
+                    if let Ok(bad_request) =
+                        BadRequest::decode(any.value.as_slice())
+                    {
+                        details.fields.extend(
+                            bad_request.field_violations.into_iter().map(
+                                |violation| {
+                                    crate::FieldError::new(
+                                        violation.field,
+                                        violation.reason,
+                                        violation.description,
+                                    )
+                                },
+                            ),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        details
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorDetails;
+
+    #[test]
+    fn test_status() {
+        let status = tonic::Status::from(crate::Error::InvalidParameter(
+            crate::ErrorDetail::new("oauth2.invalid_scope", "Invalid scope")
+                .with_field("scope", "not_allowed", "Not allowed"),
+        ));
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.message(), "Invalid scope");
+        assert_eq!(
+            ErrorDetails::from_status(&status),
+            ErrorDetails {
+                code: Some("oauth2.invalid_scope".into()),
+                fields: vec![crate::FieldError {
+                    field: "scope".into(),
+                    code: "not_allowed".into(),
+                    message: "Not allowed".into(),
+                }],
+            }
+        );
+
+        let status = tonic::Status::from(crate::Error::Unauthorized);
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert_eq!(
+            ErrorDetails::from_status(&status).code.as_deref(),
+            Some("unauthorized")
+        );
+
+        // internal details stay in the logs
+        let status =
+            tonic::Status::from(crate::Error::Database(anyhow::anyhow!("db")));
+        assert_eq!(status.code(), tonic::Code::Internal);
+        assert_eq!(status.message(), "internal error");
+        let status =
+            super::status(crate::Error::Database(anyhow::anyhow!("db")), true);
+        assert_eq!(status.code(), tonic::Code::Internal);
+        assert!(status.message().contains("db"));
+        assert_eq!(
+            ErrorDetails::from_status(&status).code.as_deref(),
+            Some("internal")
+        );
+
+        assert_eq!(
+            ErrorDetails::from_status(&tonic::Status::not_found("plain")),
+            ErrorDetails::default()
+        );
+    }
+}
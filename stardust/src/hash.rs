@@ -1,3 +1,6 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
 #[async_trait::async_trait]
 pub trait Hasher: Sync + Send {
     async fn hash(&self, password: &str) -> crate::Result<String>;
@@ -47,13 +50,28 @@ impl Hasher for Sha256Hasher {
     }
 }
 
+fn generate_salt() -> crate::Result<argon2::password_hash::SaltString> {
+    use rand_core::{OsRng, TryRngCore};
+    let mut bytes = [0u8; argon2::RECOMMENDED_SALT_LEN];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("os rng error {:?}", e))?;
+    argon2::password_hash::SaltString::encode_b64(&bytes)
+        .map_err(|e| anyhow::anyhow!("salt encode error {:?}", e).into())
+}
+
+// Salt used by hashes created before per-password salts were introduced.
+const LEGACY_ARGON2_SALT: &[u8] = b"stardust";
+
 pub struct Argon2Hasher {
     argon2: argon2::Argon2<'static>,
     config: crate::config::Argon2Config,
+    legacy_salt: String,
 }
 
 impl Argon2Hasher {
     pub fn new(config: crate::config::Argon2Config) -> crate::Result<Self> {
+        use base64::prelude::*;
         let params = argon2::Params::new(
             config.memory_kib,
             config.iterations,
@@ -69,20 +87,39 @@ impl Argon2Hasher {
                 .unwrap_or(argon2::Version::V0x13),
             params,
         );
-        Ok(Self { argon2, config })
+        let legacy_salt = BASE64_STANDARD_NO_PAD.encode(LEGACY_ARGON2_SALT);
+        Ok(Self {
+            argon2,
+            config,
+            legacy_salt,
+        })
+    }
+
+    // mixes the server side pepper into the password before hashing
+    fn peppered(&self, password: &str) -> crate::Result<Vec<u8>> {
+        use hmac::{Hmac, Mac};
+        let Some(pepper) = self.config.pepper.as_deref() else {
+            return Ok(password.as_bytes().to_vec());
+        };
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(pepper.as_bytes())
+            .map_err(|e| anyhow::anyhow!("hmac key error {:?}", e))?;
+        mac.update(password.as_bytes());
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+
+    fn is_legacy(&self, hash: &argon2::PasswordHash<'_>) -> bool {
+        hash.salt.map(|salt| salt.as_str() == self.legacy_salt).unwrap_or(false)
     }
 }
 
 #[async_trait::async_trait]
 impl Hasher for Argon2Hasher {
     async fn hash(&self, password: &str) -> crate::Result<String> {
-        use argon2::{PasswordHasher, password_hash::SaltString};
-        use base64::prelude::*;
-        let seed = BASE64_STANDARD_NO_PAD.encode(b"stardust");
-        let salt = SaltString::from_b64(&seed).unwrap();
+        use argon2::PasswordHasher;
+        let salt = generate_salt()?;
         let hash = self
             .argon2
-            .hash_password(password.as_bytes(), &salt)
+            .hash_password(&self.peppered(password)?, &salt)
             .map_err(|e| anyhow::anyhow!("argon2 hashing error {:?}", e))?
             .to_string();
         Ok(hash)
@@ -92,13 +129,22 @@ impl Hasher for Argon2Hasher {
         use argon2::password_hash::{PasswordHash, PasswordVerifier};
         let parsed = PasswordHash::new(hash)
             .map_err(|e| anyhow::anyhow!("argon2 parse error {:?}", e))?;
-        Ok(self.argon2.verify_password(password.as_bytes(), &parsed).is_ok())
+        // legacy hashes were created without the pepper
+        let password = if self.is_legacy(&parsed) {
+            password.as_bytes().to_vec()
+        } else {
+            self.peppered(password)?
+        };
+        Ok(self.argon2.verify_password(&password, &parsed).is_ok())
     }
 
     async fn needs_rehash(&self, stored_hash: &str) -> crate::Result<bool> {
         use argon2::password_hash::PasswordHash;
         let stored_hash = PasswordHash::new(stored_hash)
             .map_err(|e| anyhow::anyhow!("argon2 parse error {:?}", e))?;
+        if self.is_legacy(&stored_hash) {
+            return Ok(true);
+        }
         let required_algorithm = self
             .config
             .algorithm
@@ -135,6 +181,540 @@ impl Hasher for Argon2Hasher {
     }
 }
 
+pub struct BcryptHasher {
+    config: crate::config::BcryptConfig,
+}
+
+impl BcryptHasher {
+    pub fn new(config: crate::config::BcryptConfig) -> crate::Result<Self> {
+        // bcrypt accepts costs between 4 and 31
+        if !(4..=31).contains(&config.cost) {
+            return Err(
+                anyhow::anyhow!("invalid bcrypt cost {}", config.cost).into()
+            );
+        }
+        Self::parse_version(&config.version)?;
+        Ok(Self { config })
+    }
+
+    fn parse_version(version: &str) -> crate::Result<bcrypt::Version> {
+        match version {
+            "2a" => Ok(bcrypt::Version::TwoA),
+            "2b" => Ok(bcrypt::Version::TwoB),
+            "2x" => Ok(bcrypt::Version::TwoX),
+            "2y" => Ok(bcrypt::Version::TwoY),
+            v => Err(anyhow::anyhow!("invalid bcrypt version {}", v).into()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Hasher for BcryptHasher {
+    async fn hash(&self, password: &str) -> crate::Result<String> {
+        let hash = bcrypt::hash_with_result(password, self.config.cost)
+            .map_err(|e| anyhow::anyhow!("bcrypt hashing error {:?}", e))?
+            .format_for_version(Self::parse_version(&self.config.version)?);
+        Ok(hash)
+    }
+
+    async fn verify(&self, password: &str, hash: &str) -> crate::Result<bool> {
+        bcrypt::verify(password, hash)
+            .map_err(|e| anyhow::anyhow!("bcrypt verify error {:?}", e).into())
+    }
+
+    async fn needs_rehash(&self, stored_hash: &str) -> crate::Result<bool> {
+        let parts: bcrypt::HashParts = stored_hash
+            .parse()
+            .map_err(|e| anyhow::anyhow!("bcrypt parse error {:?}", e))?;
+        // $<version>$<cost>$<salt+hash>
+        let version = stored_hash.split('$').nth(1).unwrap_or_default();
+        if version != self.config.version {
+            return Ok(true);
+        }
+        Ok(parts.get_cost() < self.config.cost)
+    }
+}
+
+pub struct ScryptHasher {
+    params: scrypt::Params,
+}
+
+impl ScryptHasher {
+    pub fn new(config: crate::config::ScryptConfig) -> crate::Result<Self> {
+        let params = scrypt::Params::new(
+            config.log_n,
+            config.r,
+            config.p,
+            config
+                .output_len
+                .map(|v| v as usize)
+                .unwrap_or(scrypt::Params::RECOMMENDED_LEN),
+        )
+        .map_err(|e| {
+            anyhow::anyhow!("Failed to create scrypt params {:?}", e)
+        })?;
+        Ok(Self { params })
+    }
+}
+
+#[async_trait::async_trait]
+impl Hasher for ScryptHasher {
+    async fn hash(&self, password: &str) -> crate::Result<String> {
+        use scrypt::password_hash::PasswordHasher;
+        let salt = generate_salt()?;
+        let hash = scrypt::Scrypt
+            .hash_password_customized(
+                password.as_bytes(),
+                None,
+                None,
+                self.params,
+                &salt,
+            )
+            .map_err(|e| anyhow::anyhow!("scrypt hashing error {:?}", e))?
+            .to_string();
+        Ok(hash)
+    }
+
+    async fn verify(&self, password: &str, hash: &str) -> crate::Result<bool> {
+        use scrypt::password_hash::{PasswordHash, PasswordVerifier};
+        let parsed = PasswordHash::new(hash)
+            .map_err(|e| anyhow::anyhow!("scrypt parse error {:?}", e))?;
+        Ok(
+            scrypt::Scrypt
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok(),
+        )
+    }
+
+    async fn needs_rehash(&self, stored_hash: &str) -> crate::Result<bool> {
+        use scrypt::password_hash::PasswordHash;
+        let stored_hash = PasswordHash::new(stored_hash)
+            .map_err(|e| anyhow::anyhow!("scrypt parse error {:?}", e))?;
+        if stored_hash.algorithm != scrypt::ALG_ID {
+            return Ok(true);
+        }
+        let stored_params = scrypt::Params::try_from(&stored_hash)
+            .map_err(|e| anyhow::anyhow!("scrypt params error {:?}", e))?;
+        Ok(stored_params.log_n() < self.params.log_n()
+            || stored_params.r() < self.params.r()
+            || stored_params.p() < self.params.p())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashScheme {
+    NoOp,
+    Sha256,
+    Argon2,
+    Bcrypt,
+    Scrypt,
+}
+
+impl HashScheme {
+    /// detects the scheme from the stored hash prefix or PHC identifier
+    pub fn detect(hash: &str) -> Option<Self> {
+        if hash.starts_with("noop:") {
+            Some(HashScheme::NoOp)
+        } else if hash.starts_with("sha256:") {
+            Some(HashScheme::Sha256)
+        } else if ["$argon2id$", "$argon2i$", "$argon2d$"]
+            .iter()
+            .any(|p| hash.starts_with(p))
+        {
+            Some(HashScheme::Argon2)
+        } else if ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|p| hash.starts_with(p))
+        {
+            Some(HashScheme::Bcrypt)
+        } else if hash.starts_with("$scrypt$") {
+            Some(HashScheme::Scrypt)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::str::FromStr for HashScheme {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "noop" => Ok(HashScheme::NoOp),
+            "sha256" => Ok(HashScheme::Sha256),
+            "argon2" => Ok(HashScheme::Argon2),
+            "bcrypt" => Ok(HashScheme::Bcrypt),
+            "scrypt" => Ok(HashScheme::Scrypt),
+            _ => Err(anyhow::anyhow!("Invalid HashScheme: {}", s).into()),
+        }
+    }
+}
+
+/// Verifies stored hashes with the algorithm they were created with and
+/// hashes new passwords with the primary one.
+pub struct MultiHasher {
+    primary: HashScheme,
+    hashers: std::collections::HashMap<HashScheme, Arc<dyn Hasher>>,
+}
+
+impl MultiHasher {
+    pub fn new(scheme: HashScheme, hasher: Arc<dyn Hasher>) -> Self {
+        Self {
+            primary: scheme,
+            hashers: [(scheme, hasher)].into_iter().collect(),
+        }
+    }
+
+    /// registers a hasher for verifying hashes of an older scheme
+    pub fn with_legacy(
+        mut self,
+        scheme: HashScheme,
+        hasher: Arc<dyn Hasher>,
+    ) -> Self {
+        if scheme != self.primary {
+            self.hashers.insert(scheme, hasher);
+        }
+        self
+    }
+
+    fn primary(&self) -> &Arc<dyn Hasher> {
+        &self.hashers[&self.primary]
+    }
+}
+
+#[async_trait::async_trait]
+impl Hasher for MultiHasher {
+    async fn hash(&self, password: &str) -> crate::Result<String> {
+        self.primary().hash(password).await
+    }
+
+    async fn verify(&self, password: &str, hash: &str) -> crate::Result<bool> {
+        let Some(hasher) =
+            HashScheme::detect(hash).and_then(|s| self.hashers.get(&s))
+        else {
+            tracing::warn!("no hasher registered for the stored hash scheme");
+            return Ok(false);
+        };
+        hasher.verify(password, hash).await
+    }
+
+    async fn needs_rehash(&self, stored_hash: &str) -> crate::Result<bool> {
+        if HashScheme::detect(stored_hash) != Some(self.primary) {
+            return Ok(true);
+        }
+        self.primary().needs_rehash(stored_hash).await
+    }
+}
+
+/// Deterministic hasher for secrets that are looked up by their hash
+/// (api keys, refresh tokens).
+pub trait LookupHasher: Sync + Send {
+    /// digest of the secret with the current key
+    fn digest(&self, secret: &str) -> crate::Result<String>;
+    /// digests of the secret with every known key, current key first
+    fn digests(&self, secret: &str) -> crate::Result<Vec<String>>;
+}
+
+impl LookupHasher for NoOpHasher {
+    fn digest(&self, secret: &str) -> crate::Result<String> {
+        Ok(format!("noop:{}", secret))
+    }
+
+    fn digests(&self, secret: &str) -> crate::Result<Vec<String>> {
+        Ok(vec![self.digest(secret)?])
+    }
+}
+
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+pub struct HmacLookupHasher {
+    // (key id, key), the first key signs new digests
+    keys: Vec<(String, HmacSha256)>,
+}
+
+impl HmacLookupHasher {
+    pub fn new(config: crate::config::HmacConfig) -> crate::Result<Self> {
+        use hmac::Mac;
+        if config.keys.is_empty() {
+            return Err(anyhow::anyhow!("hmac hasher requires a key").into());
+        }
+        let mut keys = Vec::with_capacity(config.keys.len());
+        for key in config.keys {
+            if key.id.is_empty() || key.id.contains(':') {
+                return Err(anyhow::anyhow!(
+                    "invalid hmac key id {:?}",
+                    key.id
+                )
+                .into());
+            }
+            let mac = HmacSha256::new_from_slice(key.secret.as_bytes())
+                .map_err(|e| anyhow::anyhow!("hmac key error {:?}", e))?;
+            keys.push((key.id, mac));
+        }
+        Ok(Self { keys })
+    }
+
+    fn digest_with(id: &str, mac: &HmacSha256, secret: &str) -> String {
+        use base64::prelude::*;
+        use hmac::Mac;
+        let mut mac = mac.clone();
+        mac.update(secret.as_bytes());
+        let result = mac.finalize().into_bytes();
+        format!(
+            "hmac-sha256:{}:{}",
+            id,
+            BASE64_STANDARD_NO_PAD.encode(result)
+        )
+    }
+}
+
+impl LookupHasher for HmacLookupHasher {
+    fn digest(&self, secret: &str) -> crate::Result<String> {
+        let (id, mac) = &self.keys[0];
+        Ok(Self::digest_with(id, mac, secret))
+    }
+
+    fn digests(&self, secret: &str) -> crate::Result<Vec<String>> {
+        Ok(self
+            .keys
+            .iter()
+            .map(|(id, mac)| Self::digest_with(id, mac, secret))
+            .collect())
+    }
+}
+
+/// Lookup hasher that still finds secrets digested by its legacy hashers,
+/// for switching `hashing.secret.algorithm` without losing the api keys
+/// and refresh tokens already issued. New digests come from the primary;
+/// refresh tokens move to it on their next refresh, api keys keep the old
+/// digest until they are reissued.
+pub struct MultiLookupHasher {
+    primary: Arc<dyn LookupHasher>,
+    legacy: Vec<Arc<dyn LookupHasher>>,
+}
+
+impl MultiLookupHasher {
+    pub fn new(primary: Arc<dyn LookupHasher>) -> Self {
+        Self {
+            primary,
+            legacy: Vec::new(),
+        }
+    }
+
+    pub fn with_legacy(mut self, hasher: Arc<dyn LookupHasher>) -> Self {
+        self.legacy.push(hasher);
+        self
+    }
+}
+
+impl LookupHasher for MultiLookupHasher {
+    fn digest(&self, secret: &str) -> crate::Result<String> {
+        self.primary.digest(secret)
+    }
+
+    fn digests(&self, secret: &str) -> crate::Result<Vec<String>> {
+        let mut digests = self.primary.digests(secret)?;
+        for hasher in &self.legacy {
+            for digest in hasher.digests(secret)? {
+                if !digests.contains(&digest) {
+                    digests.push(digest);
+                }
+            }
+        }
+        Ok(digests)
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct HashingPoolStats {
+    pub running: usize,
+    pub queued: usize,
+    pub rejected: u64,
+}
+
+/// Runs CPU heavy hashing on the blocking thread pool, bounded by a
+/// semaphore so a burst of logins can't starve the runtime.
+pub struct HashingPool {
+    config: crate::config::HashingPoolConfig,
+    semaphore: Arc<tokio::sync::Semaphore>,
+    queued: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+impl HashingPool {
+    pub fn new(config: crate::config::HashingPoolConfig) -> Self {
+        let semaphore =
+            Arc::new(tokio::sync::Semaphore::new(config.max_blocking.max(1)));
+        Self {
+            config,
+            semaphore,
+            queued: AtomicUsize::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> HashingPoolStats {
+        HashingPoolStats {
+            running: self.config.max_blocking.max(1)
+                - self.semaphore.available_permits(),
+            queued: self.queued.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    pub async fn run<F, T>(&self, f: F) -> crate::Result<T>
+    where
+        F: FnOnce() -> crate::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self.acquire().await?;
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            f()
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("hashing task failed {:?}", e))?
+    }
+
+    async fn acquire(
+        &self,
+    ) -> crate::Result<tokio::sync::OwnedSemaphorePermit> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+        let queued = self.queued.fetch_add(1, Ordering::Relaxed);
+        let result = if queued >= self.config.queue_size {
+            Err(crate::Error::Timeout)
+        } else {
+            tokio::time::timeout(
+                std::time::Duration::from_millis(self.config.queue_timeout_ms),
+                self.semaphore.clone().acquire_owned(),
+            )
+            .await
+            .map_err(|_| crate::Error::Timeout)
+            .and_then(|permit| {
+                permit.map_err(|e| {
+                    anyhow::anyhow!("hashing pool closed {:?}", e).into()
+                })
+            })
+        };
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        if result.is_err() {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!("hashing pool is saturated: {:?}", self.stats());
+        }
+        result
+    }
+}
+
+/// Hasher running the wrapped hasher on a `HashingPool`.
+pub struct BlockingHasher {
+    inner: Arc<dyn Hasher>,
+    pool: Arc<HashingPool>,
+}
+
+impl BlockingHasher {
+    pub fn new(inner: Arc<dyn Hasher>, pool: Arc<HashingPool>) -> Self {
+        Self { inner, pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl Hasher for BlockingHasher {
+    async fn hash(&self, password: &str) -> crate::Result<String> {
+        let (inner, password) = (self.inner.clone(), password.to_owned());
+        let runtime = tokio::runtime::Handle::current();
+        self.pool.run(move || runtime.block_on(inner.hash(&password))).await
+    }
+
+    async fn verify(&self, password: &str, hash: &str) -> crate::Result<bool> {
+        let (inner, password) = (self.inner.clone(), password.to_owned());
+        let hash = hash.to_owned();
+        let runtime = tokio::runtime::Handle::current();
+        self.pool
+            .run(move || runtime.block_on(inner.verify(&password, &hash)))
+            .await
+    }
+
+    async fn needs_rehash(&self, stored_hash: &str) -> crate::Result<bool> {
+        // only parses the stored hash, cheap enough to stay on the runtime
+        self.inner.needs_rehash(stored_hash).await
+    }
+}
+
+fn scheme_hasher(
+    scheme: HashScheme,
+    config: &crate::config::PasswordHashingConfig,
+) -> crate::Result<Arc<dyn Hasher>> {
+    let hasher: Arc<dyn Hasher> = match scheme {
+        HashScheme::NoOp => Arc::new(NoOpHasher),
+        HashScheme::Sha256 => Arc::new(Sha256Hasher),
+        HashScheme::Argon2 => Arc::new(Argon2Hasher::new(
+            config.argon2.clone().unwrap_or_default(),
+        )?),
+        HashScheme::Bcrypt => Arc::new(BcryptHasher::new(
+            config.bcrypt.clone().unwrap_or_default(),
+        )?),
+        HashScheme::Scrypt => Arc::new(ScryptHasher::new(
+            config.scrypt.clone().unwrap_or_default(),
+        )?),
+    };
+    Ok(hasher)
+}
+
+/// Creates the password hasher selected by the config. Legacy schemes are
+/// verified and flagged for rehash.
+pub fn password_hasher(
+    config: &crate::config::PasswordHashingConfig,
+) -> crate::Result<Arc<dyn Hasher>> {
+    let scheme: HashScheme = config.algorithm.parse()?;
+    let hasher = scheme_hasher(scheme, config)?;
+    if config.legacy.is_empty() {
+        return Ok(hasher);
+    }
+    let mut multi = MultiHasher::new(scheme, hasher);
+    for legacy in config.legacy.iter() {
+        let legacy: HashScheme = legacy.parse()?;
+        multi = multi.with_legacy(legacy, scheme_hasher(legacy, config)?);
+    }
+    Ok(Arc::new(multi))
+}
+
+/// Creates the lookup hasher selected by the config, also finding digests
+/// of the `legacy` schemes when there are any.
+pub fn secret_hasher(
+    config: &crate::config::SecretHashingConfig,
+) -> crate::Result<Arc<dyn LookupHasher>> {
+    let primary = lookup_hasher(&config.algorithm, config)?;
+    if config.legacy.is_empty() {
+        return Ok(primary);
+    }
+    let mut multi = MultiLookupHasher::new(primary);
+    for legacy in &config.legacy {
+        multi = multi.with_legacy(lookup_hasher(legacy, config)?);
+    }
+    Ok(Arc::new(multi))
+}
+
+fn lookup_hasher(
+    algorithm: &str,
+    config: &crate::config::SecretHashingConfig,
+) -> crate::Result<Arc<dyn LookupHasher>> {
+    match algorithm {
+        "noop" => Ok(Arc::new(NoOpHasher)),
+        "hmac" => {
+            let Some(hmac) = config.hmac.clone() else {
+                return Err(anyhow::anyhow!(
+                    "hashing.secret.hmac is required for hmac"
+                )
+                .into());
+            };
+            Ok(Arc::new(HmacLookupHasher::new(hmac)?))
+        }
+        v => Err(anyhow::anyhow!("Invalid secret hasher: {}", v).into()),
+    }
+}
+
 impl Default for crate::config::Argon2Config {
     fn default() -> Self {
         Self {
@@ -144,6 +724,39 @@ impl Default for crate::config::Argon2Config {
             algorithm: "argon2id".into(),
             version: 0x13,
             output_len: Some(32),
+            pepper: None,
+        }
+    }
+}
+
+impl Default for crate::config::HashingPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_blocking: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            queue_size: 256,
+            queue_timeout_ms: 5000,
+        }
+    }
+}
+
+impl Default for crate::config::BcryptConfig {
+    fn default() -> Self {
+        Self {
+            cost: bcrypt::DEFAULT_COST,
+            version: "2b".into(),
+        }
+    }
+}
+
+impl Default for crate::config::ScryptConfig {
+    fn default() -> Self {
+        Self {
+            log_n: scrypt::Params::RECOMMENDED_LOG_N,
+            r: scrypt::Params::RECOMMENDED_R,
+            p: scrypt::Params::RECOMMENDED_P,
+            output_len: Some(scrypt::Params::RECOMMENDED_LEN as u32),
         }
     }
 }
@@ -212,4 +825,324 @@ mod tests {
         assert!(result);
         assert!(needs_rehash);
     }
+
+    #[tokio::test]
+    async fn test_bcrypt_hasher() {
+        let config = crate::config::BcryptConfig {
+            cost: 4,
+            ..Default::default()
+        };
+        let hasher = super::BcryptHasher::new(config.clone()).unwrap();
+        let password = "password";
+        let default_hash = hasher.hash(password).await.unwrap();
+        assert!(default_hash.starts_with("$2b$04$"));
+
+        let result = hasher.verify(password, &default_hash).await.unwrap();
+        let needs_rehash = hasher.needs_rehash(&default_hash).await.unwrap();
+        assert!(result);
+        assert!(!needs_rehash);
+
+        let result =
+            hasher.verify("wrongpassword", &default_hash).await.unwrap();
+        let needs_rehash = hasher.needs_rehash(&default_hash).await.unwrap();
+        assert!(!result);
+        assert!(!needs_rehash);
+
+        let mut newcfg = config.clone();
+        newcfg.cost = 5;
+        let new_hasher = super::BcryptHasher::new(newcfg).unwrap();
+        let result = new_hasher.verify(password, &default_hash).await.unwrap();
+        let needs_rehash =
+            new_hasher.needs_rehash(&default_hash).await.unwrap();
+        assert!(result);
+        assert!(needs_rehash);
+
+        let mut newcfg = config.clone();
+        newcfg.version = "2a".into();
+        let new_hasher = super::BcryptHasher::new(newcfg).unwrap();
+        let result = new_hasher.verify(password, &default_hash).await.unwrap();
+        let needs_rehash =
+            new_hasher.needs_rehash(&default_hash).await.unwrap();
+        assert!(result);
+        assert!(needs_rehash);
+    }
+
+    #[tokio::test]
+    async fn test_scrypt_hasher() {
+        let config = crate::config::ScryptConfig {
+            log_n: 10,
+            ..Default::default()
+        };
+        let hasher = super::ScryptHasher::new(config.clone()).unwrap();
+        let password = "password";
+        let default_hash = hasher.hash(password).await.unwrap();
+        assert_ne!(default_hash, hasher.hash(password).await.unwrap());
+
+        let result = hasher.verify(password, &default_hash).await.unwrap();
+        let needs_rehash = hasher.needs_rehash(&default_hash).await.unwrap();
+        assert!(result);
+        assert!(!needs_rehash);
+
+        let result =
+            hasher.verify("wrongpassword", &default_hash).await.unwrap();
+        let needs_rehash = hasher.needs_rehash(&default_hash).await.unwrap();
+        assert!(!result);
+        assert!(!needs_rehash);
+
+        let mut newcfg = config.clone();
+        newcfg.log_n = 11;
+        let new_hasher = super::ScryptHasher::new(newcfg).unwrap();
+        let result = new_hasher.verify(password, &default_hash).await.unwrap();
+        let needs_rehash =
+            new_hasher.needs_rehash(&default_hash).await.unwrap();
+        assert!(result);
+        assert!(needs_rehash);
+
+        let mut newcfg = config.clone();
+        newcfg.r = 16;
+        let new_hasher = super::ScryptHasher::new(newcfg).unwrap();
+        let result = new_hasher.verify(password, &default_hash).await.unwrap();
+        let needs_rehash =
+            new_hasher.needs_rehash(&default_hash).await.unwrap();
+        assert!(result);
+        assert!(needs_rehash);
+    }
+
+    #[tokio::test]
+    async fn test_argon_hasher_salt() {
+        let config = crate::config::Argon2Config::default();
+        let hasher = super::Argon2Hasher::new(config.clone()).unwrap();
+        let password = "password";
+        let hash1 = hasher.hash(password).await.unwrap();
+        let hash2 = hasher.hash(password).await.unwrap();
+        assert_ne!(hash1, hash2);
+        assert!(hasher.verify(password, &hash1).await.unwrap());
+        assert!(hasher.verify(password, &hash2).await.unwrap());
+
+        // hash created with the fixed salt of earlier versions
+        use argon2::{PasswordHasher, password_hash::SaltString};
+        use base64::prelude::*;
+        let seed = BASE64_STANDARD_NO_PAD.encode(LEGACY_ARGON2_SALT);
+        let salt = SaltString::from_b64(&seed).unwrap();
+        let legacy_hash = hasher
+            .argon2
+            .hash_password(password.as_bytes(), &salt)
+            .unwrap()
+            .to_string();
+        assert!(hasher.verify(password, &legacy_hash).await.unwrap());
+        assert!(hasher.needs_rehash(&legacy_hash).await.unwrap());
+
+        let mut newcfg = config.clone();
+        newcfg.pepper = Some("pepper".into());
+        let new_hasher = super::Argon2Hasher::new(newcfg).unwrap();
+        assert!(new_hasher.verify(password, &legacy_hash).await.unwrap());
+        assert!(new_hasher.needs_rehash(&legacy_hash).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_argon_hasher_pepper() {
+        let config = crate::config::Argon2Config {
+            pepper: Some("pepper".into()),
+            ..Default::default()
+        };
+        let hasher = super::Argon2Hasher::new(config.clone()).unwrap();
+        let password = "password";
+        let hash = hasher.hash(password).await.unwrap();
+        assert!(hasher.verify(password, &hash).await.unwrap());
+        assert!(!hasher.verify("wrongpassword", &hash).await.unwrap());
+        assert!(!hasher.needs_rehash(&hash).await.unwrap());
+
+        let mut newcfg = config.clone();
+        newcfg.pepper = None;
+        let new_hasher = super::Argon2Hasher::new(newcfg).unwrap();
+        assert!(!new_hasher.verify(password, &hash).await.unwrap());
+
+        let mut newcfg = config.clone();
+        newcfg.pepper = Some("other".into());
+        let new_hasher = super::Argon2Hasher::new(newcfg).unwrap();
+        assert!(!new_hasher.verify(password, &hash).await.unwrap());
+    }
+
+    #[test]
+    fn test_hmac_lookup_hasher() {
+        use crate::config::{HmacConfig, HmacKeyConfig};
+        let key = |id: &str, secret: &str| HmacKeyConfig {
+            id: id.into(),
+            secret: secret.into(),
+        };
+        let hasher = HmacLookupHasher::new(HmacConfig {
+            keys: vec![key("k1", "secret1")],
+        })
+        .unwrap();
+        let digest = hasher.digest("apikey").unwrap();
+        assert!(digest.starts_with("hmac-sha256:k1:"));
+        assert_eq!(digest, hasher.digest("apikey").unwrap());
+        assert_ne!(digest, hasher.digest("other").unwrap());
+
+        let rotated = HmacLookupHasher::new(HmacConfig {
+            keys: vec![key("k2", "secret2"), key("k1", "secret1")],
+        })
+        .unwrap();
+        let digests = rotated.digests("apikey").unwrap();
+        assert_eq!(digests.len(), 2);
+        assert_eq!(digests[0], rotated.digest("apikey").unwrap());
+        assert_ne!(digests[0], digest);
+        assert_eq!(digests[1], digest);
+
+        assert!(HmacLookupHasher::new(HmacConfig { keys: vec![] }).is_err());
+        assert!(
+            HmacLookupHasher::new(HmacConfig {
+                keys: vec![key("k:1", "secret1")],
+            })
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multi_hasher() {
+        let argon2 = Arc::new(
+            Argon2Hasher::new(crate::config::Argon2Config::default()).unwrap(),
+        );
+        let hasher = MultiHasher::new(HashScheme::Argon2, argon2)
+            .with_legacy(HashScheme::NoOp, Arc::new(NoOpHasher))
+            .with_legacy(HashScheme::Sha256, Arc::new(Sha256Hasher));
+        let password = "password";
+
+        let hash = hasher.hash(password).await.unwrap();
+        assert_eq!(HashScheme::detect(&hash), Some(HashScheme::Argon2));
+        assert!(hasher.verify(password, &hash).await.unwrap());
+        assert!(!hasher.needs_rehash(&hash).await.unwrap());
+
+        for legacy_hash in [
+            NoOpHasher.hash(password).await.unwrap(),
+            Sha256Hasher.hash(password).await.unwrap(),
+        ] {
+            assert!(hasher.verify(password, &legacy_hash).await.unwrap());
+            assert!(!hasher.verify("wrong", &legacy_hash).await.unwrap());
+            assert!(hasher.needs_rehash(&legacy_hash).await.unwrap());
+        }
+
+        // unregistered or unknown schemes never verify
+        let bcrypt_hash =
+            "$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW";
+        assert_eq!(HashScheme::detect(bcrypt_hash), Some(HashScheme::Bcrypt));
+        assert!(!hasher.verify(password, bcrypt_hash).await.unwrap());
+        assert!(hasher.needs_rehash(bcrypt_hash).await.unwrap());
+        assert!(!hasher.verify(password, "plain").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_password_hasher_factory() {
+        let mut config = crate::config::PasswordHashingConfig {
+            algorithm: "bcrypt".into(),
+            legacy: vec!["noop".into()],
+            argon2: None,
+            bcrypt: Some(crate::config::BcryptConfig {
+                cost: 4,
+                ..Default::default()
+            }),
+            scrypt: None,
+        };
+        let hasher = password_hasher(&config).unwrap();
+        let password = "password";
+        let hash = hasher.hash(password).await.unwrap();
+        assert_eq!(HashScheme::detect(&hash), Some(HashScheme::Bcrypt));
+        assert!(!hasher.needs_rehash(&hash).await.unwrap());
+
+        let legacy_hash = NoOpHasher.hash(password).await.unwrap();
+        assert!(hasher.verify(password, &legacy_hash).await.unwrap());
+        assert!(hasher.needs_rehash(&legacy_hash).await.unwrap());
+
+        config.bcrypt.as_mut().unwrap().version = "2x".into();
+        let hasher = password_hasher(&config).unwrap();
+        let hash = hasher.hash(password).await.unwrap();
+        assert!(hash.starts_with("$2x$"));
+        assert_eq!(HashScheme::detect(&hash), Some(HashScheme::Bcrypt));
+        assert!(hasher.verify(password, &hash).await.unwrap());
+
+        config.algorithm = "unknown".into();
+        assert!(password_hasher(&config).is_err());
+
+        let config = crate::config::Config::test_config();
+        assert!(secret_hasher(&config.hashing.secret).is_ok());
+        assert!(
+            secret_hasher(&crate::config::SecretHashingConfig {
+                algorithm: "hmac".into(),
+                legacy: vec![],
+                hmac: None,
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_secret_hasher_legacy() {
+        let mut config = crate::config::Config::test_config().hashing.secret;
+        config.legacy = vec!["noop".into()];
+        let hasher = secret_hasher(&config).unwrap();
+        let digest = hasher.digest("apikey").unwrap();
+        assert!(digest.starts_with("hmac-sha256:"));
+        // keys issued before the switch are still found
+        assert_eq!(
+            hasher.digests("apikey").unwrap(),
+            [digest, NoOpHasher.digest("apikey").unwrap()]
+        );
+
+        config.legacy = vec!["md5".into()];
+        assert!(secret_hasher(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_hasher() {
+        let pool =
+            Arc::new(HashingPool::new(crate::config::HashingPoolConfig {
+                max_blocking: 1,
+                queue_size: 0,
+                queue_timeout_ms: 100,
+            }));
+        let hasher = BlockingHasher::new(Arc::new(NoOpHasher), pool.clone());
+        let hash = hasher.hash("password").await.unwrap();
+        assert!(hasher.verify("password", &hash).await.unwrap());
+        assert!(!hasher.needs_rehash(&hash).await.unwrap());
+
+        let busy = pool.clone();
+        let task = tokio::spawn(async move {
+            busy.run(|| {
+                std::thread::sleep(std::time::Duration::from_millis(300));
+                Ok(())
+            })
+            .await
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(pool.stats().running, 1);
+        let result = hasher.hash("password").await;
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+        assert_eq!(pool.stats().rejected, 1);
+        task.await.unwrap().unwrap();
+        assert_eq!(pool.stats().running, 0);
+        assert_eq!(pool.stats().queued, 0);
+    }
+
+    #[tokio::test]
+    async fn test_hashing_pool_queue_timeout() {
+        let pool =
+            Arc::new(HashingPool::new(crate::config::HashingPoolConfig {
+                max_blocking: 1,
+                queue_size: 1,
+                queue_timeout_ms: 50,
+            }));
+        let busy = pool.clone();
+        let task = tokio::spawn(async move {
+            busy.run(|| {
+                std::thread::sleep(std::time::Duration::from_millis(300));
+                Ok(())
+            })
+            .await
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let result = pool.run(|| Ok(())).await;
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+        task.await.unwrap().unwrap();
+        assert!(pool.run(|| Ok(1)).await.is_ok());
+    }
 }
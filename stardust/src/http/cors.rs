@@ -0,0 +1,77 @@
+//! Cors headers for the origins of `server.cors`, looked up on every
+//! request so reloaded origins apply at once.
+use std::sync::Arc;
+
+use axum::http::{HeaderName, HeaderValue};
+use tokio::sync::watch;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::config::Config;
+
+pub fn cors_layer(config: watch::Receiver<Arc<Config>>) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            is_allowed(&config.borrow(), origin)
+        }))
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([HeaderName::from_static("x-trace-id")])
+}
+
+fn is_allowed(config: &Config, origin: &HeaderValue) -> bool {
+    let Some(cors) = &config.server.cors else {
+        return false;
+    };
+    cors.allowed_origins
+        .iter()
+        .any(|allowed| allowed == "*" || origin == allowed.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::{Request, header};
+
+    async fn allowed_origin(
+        router: &axum::Router,
+        origin: &str,
+    ) -> Option<String> {
+        let response = tower::ServiceExt::oneshot(
+            router.clone(),
+            Request::get("/")
+                .header(header::ORIGIN, origin)
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .map(|v| v.to_str().unwrap().to_owned())
+    }
+
+    #[tokio::test]
+    async fn test_cors_layer() {
+        let mut config = crate::config::Config::test_config();
+        config.server.cors = Some(crate::config::CorsConfig {
+            allowed_origins: vec!["https://example.com".into()],
+        });
+        let (sender, receiver) = tokio::sync::watch::channel(Arc::new(config));
+        let router = axum::Router::new()
+            .route("/", axum::routing::get(|| async {}))
+            .layer(super::cors_layer(receiver));
+
+        assert_eq!(
+            allowed_origin(&router, "https://example.com").await.as_deref(),
+            Some("https://example.com")
+        );
+        assert_eq!(allowed_origin(&router, "https://evil.com").await, None);
+
+        sender.send_modify(|config| {
+            Arc::make_mut(config).server.cors = None;
+        });
+        assert_eq!(allowed_origin(&router, "https://example.com").await, None);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod cors;
+pub mod problem;
+pub mod ratelimit;
 pub mod session;
 pub mod traceid;
 pub mod utils;
@@ -16,99 +19,101 @@ pub async fn run_server(
     ))
     .await
     .map_err(|e| anyhow!("tcp bind failed: {:?}", e))?;
-    axum::serve(listener, router)
-        .await
-        .map_err(|e| anyhow!("http serve failed: {:?}", e))?;
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .map_err(|e| anyhow!("http serve failed: {:?}", e))?;
     Ok(())
 }
 
+/// Turns error responses that aren't json, like axum's extractor
+/// rejections, into problem+json with the body as `detail`. The state is
+/// `server.expose_internal_errors`: 5xx details only reach the client when
+/// it is set.
 pub async fn map_response(
+    axum::extract::State(expose): axum::extract::State<bool>,
     request: axum::http::Request<axum::body::Body>,
     next: axum::middleware::Next,
 ) -> axum::response::Response {
     let response = next.run(request).await;
-
-    match response.status() {
-        s if s == axum::http::StatusCode::UNPROCESSABLE_ENTITY
-            || s == axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE =>
-        {
-            if !utils::is_json(&response.headers()) {
-                let (mut parts, body) = response.into_parts();
-                // build custom content if require
-                let mut content = String::new();
-                content.push_str(r#"{"message":"#);
-                content.push_str(
-                    &utils::into_string(body).await.unwrap_or_else(|e| {
-                        tracing::warn!("Failed to read response body: {:?}", e);
-                        format!("Failed to read response body {:?}", e)
-                    }),
-                );
-                content.push_str(r#"}"#);
-
-                parts.headers.extend([
-                    (
-                        axum::http::header::CONTENT_TYPE,
-                        axum::http::header::HeaderValue::from_static(
-                            "application/json",
-                        ),
-                    ),
-                    (
-                        axum::http::header::CONTENT_LENGTH,
-                        axum::http::header::HeaderValue::from(
-                            content.len() as u64
-                        ),
-                    ),
-                ]);
-                return Response::from_parts(
-                    parts,
-                    axum::body::Body::from(content),
-                );
-            }
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+    if let Some(problem::InternalError(error)) =
+        response.extensions().get::<problem::InternalError>().cloned()
+    {
+        if !expose {
+            return response;
         }
-        _ => {}
+        let (parts, _) = response.into_parts();
+        let problem = problem::Problem::from_error(status, &error, true);
+        return with_problem(parts, problem);
+    }
+    if utils::is_json(response.headers())
+        || problem::is_problem(response.headers())
+    {
+        return response;
     }
 
-    response
+    let (parts, body) = response.into_parts();
+    let body = utils::into_string(body).await.unwrap_or_else(|e| {
+        tracing::warn!("Failed to read response body: {:?}", e);
+        String::new()
+    });
+    let detail = if status.is_server_error() {
+        tracing::error!("{} {}", status, body);
+        expose.then_some(body)
+    } else {
+        Some(body)
+    };
+    with_problem(parts, problem::Problem::new(status, detail))
+}
+
+fn with_problem(
+    mut parts: axum::http::response::Parts,
+    mut problem: problem::Problem,
+) -> axum::response::Response {
+    // this layer may run outside of `TraceIdLayer`, whose header is set
+    if problem.trace_id.is_none() {
+        problem.trace_id = parts
+            .headers
+            .get("x-trace-id")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+    }
+    let response = problem.into_response();
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    parts.headers.extend(response.headers().clone());
+    Response::from_parts(parts, response.into_body())
 }
 
 impl IntoResponse for crate::Error {
     fn into_response(self) -> axum::response::Response {
-        match &self {
-            crate::Error::InvalidParameter(_) => {
-                (axum::http::StatusCode::BAD_REQUEST, format!("{:?}", self))
-                    .into_response()
-            }
-            crate::Error::IllegalState(_) => (
-                axum::http::StatusCode::PRECONDITION_FAILED,
-                format!("{:?}", self),
-            )
-                .into_response(),
-            crate::Error::AlreadyExists(_) => {
-                (axum::http::StatusCode::CONFLICT, format!("{:?}", self))
-                    .into_response()
-            }
-            crate::Error::NotFound(_) => {
-                (axum::http::StatusCode::NOT_FOUND, format!("{:?}", self))
-                    .into_response()
-            }
-            crate::Error::Timeout => (
-                axum::http::StatusCode::REQUEST_TIMEOUT,
-                format!("{:?}", self),
-            )
-                .into_response(),
-            crate::Error::Unauthorized => {
-                (axum::http::StatusCode::UNAUTHORIZED, format!("{:?}", self))
-                    .into_response()
-            }
-            crate::Error::Forbidden => {
-                (axum::http::StatusCode::FORBIDDEN, format!("{:?}", self))
-                    .into_response()
+        use axum::http::StatusCode;
+
+        let status = match &self {
+            crate::Error::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+            crate::Error::IllegalState(_) => StatusCode::PRECONDITION_FAILED,
+            crate::Error::AlreadyExists(_) => StatusCode::CONFLICT,
+            crate::Error::NotFound(_) => StatusCode::NOT_FOUND,
+            crate::Error::Timeout => StatusCode::REQUEST_TIMEOUT,
+            crate::Error::Unauthorized => StatusCode::UNAUTHORIZED,
+            crate::Error::Forbidden => StatusCode::FORBIDDEN,
+            crate::Error::Unhandled(_) | crate::Error::Database(_) => {
+                tracing::error!("{:?}", self);
+                StatusCode::INTERNAL_SERVER_ERROR
             }
-            _ => (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                format!("{:?}", self),
-            )
-                .into_response(),
+        };
+        let mut response =
+            problem::Problem::from_error(status, &self, false).into_response();
+        if status.is_server_error() {
+            response
+                .extensions_mut()
+                .insert(problem::InternalError(std::sync::Arc::new(self)));
         }
+        response
     }
 }
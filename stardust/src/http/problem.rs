@@ -0,0 +1,228 @@
+//! RFC 7807 `application/problem+json` bodies, shared by every error
+//! response.
+use std::sync::Arc;
+
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::IntoResponse;
+
+pub const CONTENT_TYPE: &str = "application/problem+json";
+
+/// The internal error behind a redacted 5xx response, for `map_response`
+/// to expose when `server.expose_internal_errors` is set.
+#[derive(Clone)]
+pub(crate) struct InternalError(pub Arc<crate::Error>);
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>, // `Error::code`, stable across releases
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<crate::FieldError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>, // x-trace-id of the request
+}
+
+impl Problem {
+    /// A problem with no type of its own, titled after `status` and tagged
+    /// with the current request's trace id.
+    pub fn new(status: StatusCode, detail: Option<String>) -> Self {
+        Self {
+            type_uri: "about:blank".into(),
+            title: status.canonical_reason().unwrap_or_default().into(),
+            status: status.as_u16(),
+            detail: detail.filter(|detail| !detail.is_empty()),
+            code: None,
+            errors: Vec::new(),
+            trace_id: super::traceid::current(),
+        }
+    }
+
+    /// The problem of an error, with its code and field errors. Internal
+    /// errors are only described when `expose` is set.
+    pub fn from_error(
+        status: StatusCode,
+        error: &crate::Error,
+        expose: bool,
+    ) -> Self {
+        let detail = match error {
+            crate::Error::Unhandled(_) | crate::Error::Database(_) => {
+                expose.then(|| format!("{:?}", error))
+            }
+            _ => error.detail().map(|detail| detail.message.to_string()),
+        };
+        Self {
+            code: Some(error.code().to_owned()),
+            errors: error.fields().to_vec(),
+            ..Self::new(status, detail)
+        }
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> axum::response::Response {
+        let status = StatusCode::from_u16(self.status)
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, axum::Json(self)).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(CONTENT_TYPE),
+        );
+        response
+    }
+}
+
+pub fn is_problem(headers: &axum::http::HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(CONTENT_TYPE))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    use super::Problem;
+
+    async fn problem(
+        response: axum::response::Response,
+    ) -> (StatusCode, String, Problem) {
+        let status = response.status();
+        let content_type = response.headers()[axum::http::header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body =
+            crate::http::utils::into_bytes(response.into_body()).await.unwrap();
+        (status, content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_error_response() {
+        let (status, content_type, body) =
+            crate::http::traceid::scope("trace-1".into(), async {
+                problem(
+                    crate::Error::InvalidParameter(
+                        crate::ErrorDetail::new(
+                            "oauth2.invalid_scope",
+                            "Invalid scope",
+                        )
+                        .with_field(
+                            "scope",
+                            "not_allowed",
+                            "Not allowed for the client",
+                        ),
+                    )
+                    .into_response(),
+                )
+                .await
+            })
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type, super::CONTENT_TYPE);
+        assert_eq!(
+            body,
+            Problem {
+                type_uri: "about:blank".into(),
+                title: "Bad Request".into(),
+                status: 400,
+                detail: Some("Invalid scope".into()),
+                code: Some("oauth2.invalid_scope".into()),
+                errors: vec![crate::FieldError {
+                    field: "scope".into(),
+                    code: "not_allowed".into(),
+                    message: "Not allowed for the client".into(),
+                }],
+                trace_id: Some("trace-1".into()),
+            }
+        );
+
+        let (_, _, body) =
+            problem(crate::Error::NotFound("".into()).into_response()).await;
+        assert_eq!((body.status, body.detail), (404, None));
+        assert_eq!(body.code.as_deref(), Some("not_found"));
+
+        // internal details stay in the logs unless exposed
+        let error = || {
+            crate::Error::Database(anyhow::anyhow!("relation does not exist"))
+        };
+        let (status, _, body) = problem(error().into_response()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body.title, "Internal Server Error");
+        assert_eq!(body.detail, None);
+        assert_eq!(body.code.as_deref(), Some("internal"));
+        for expose in [false, true] {
+            let router = axum::Router::new()
+                .route(
+                    "/error",
+                    axum::routing::get(move || async move {
+                        crate::Result::<()>::Err(error())
+                    }),
+                )
+                .layer(crate::http::traceid::TraceIdLayer)
+                .layer(axum::middleware::from_fn_with_state(
+                    expose,
+                    crate::http::map_response,
+                ));
+            let response = tower::ServiceExt::oneshot(
+                router,
+                axum::http::Request::get("/error")
+                    .header("x-trace-id", "trace-1")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+            let (status, content_type, body) = problem(response).await;
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(content_type, super::CONTENT_TYPE);
+            assert_eq!(body.code.as_deref(), Some("internal"));
+            assert_eq!(body.trace_id.as_deref(), Some("trace-1"));
+            assert_eq!(
+                body.detail
+                    .is_some_and(|d| d.contains("relation does not exist")),
+                expose
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_map_response() {
+        let router =
+            axum::Router::new()
+                .route(
+                    "/json",
+                    axum::routing::post(
+                        |_: axum::Json<serde_json::Value>| async {},
+                    ),
+                )
+                .layer(crate::http::traceid::TraceIdLayer)
+                .layer(axum::middleware::from_fn_with_state(
+                    false,
+                    crate::http::map_response,
+                ));
+        let response = tower::ServiceExt::oneshot(
+            router,
+            axum::http::Request::post("/json")
+                .header("content-type", "application/json")
+                .header("x-trace-id", "trace-1")
+                .body(axum::body::Body::from("{"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        let (status, content_type, body) = problem(response).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type, super::CONTENT_TYPE);
+        assert_eq!(body.title, "Bad Request");
+        assert!(body.detail.unwrap().contains("EOF"));
+        assert_eq!(body.trace_id.as_deref(), Some("trace-1"));
+    }
+}
@@ -0,0 +1,222 @@
+//! Token buckets per client address for `server.rate_limit`, looked up on
+//! every request so a reloaded limit applies at once.
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::IntoResponse;
+use tokio::sync::watch;
+
+use crate::config::Config;
+
+// buckets kept at most, the least recently seen clients go first
+const MAX_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// `None` for requests served without `ConnectInfo`, sharing a bucket
+type Client = Option<IpAddr>;
+
+/// Two generations of buckets: a client seen is moved into `current`, and
+/// once that holds half of `MAX_CLIENTS` it replaces `previous`, dropping
+/// the clients not seen since the swap before.
+#[derive(Default)]
+struct Buckets {
+    current: HashMap<Client, Bucket>,
+    previous: HashMap<Client, Bucket>,
+}
+
+impl Buckets {
+    fn get(&mut self, client: Client, fresh: Bucket) -> &mut Bucket {
+        if !self.current.contains_key(&client) {
+            let bucket = self.previous.remove(&client).unwrap_or(fresh);
+            if self.current.len() >= MAX_CLIENTS / 2 {
+                self.previous = std::mem::take(&mut self.current);
+            }
+            self.current.insert(client, bucket);
+        }
+        self.current.get_mut(&client).unwrap()
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.current.len() + self.previous.len()
+    }
+}
+
+pub struct RateLimiter {
+    config: watch::Receiver<Arc<Config>>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: watch::Receiver<Arc<Config>>) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            buckets: Mutex::new(Buckets::default()),
+        })
+    }
+
+    /// Takes a token of `client`'s bucket, or tells how long until the
+    /// next one.
+    fn acquire(
+        &self,
+        client: Option<IpAddr>,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let Some(limit) = self.config.borrow().server.rate_limit.clone() else {
+            return Ok(());
+        };
+        let rate = limit.requests_per_second as f64;
+        let burst = limit.burst as f64;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get(
+            client,
+            Bucket {
+                tokens: burst,
+                updated: now,
+            },
+        );
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+/// Middleware answering 429 with `Retry-After` to clients over the limit,
+/// for `axum::middleware::from_fn_with_state` with a `RateLimiter`.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    match limiter.acquire(client, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            let mut response = super::problem::Problem {
+                code: Some("rate_limited".into()),
+                ..super::problem::Problem::new(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    None,
+                )
+            }
+            .into_response();
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+            );
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_acquire() {
+        let mut config = crate::config::Config::test_config();
+        config.server.rate_limit = Some(crate::config::RateLimitConfig {
+            requests_per_second: 1,
+            burst: 2,
+        });
+        let (sender, receiver) = tokio::sync::watch::channel(Arc::new(config));
+        let limiter = super::RateLimiter::new(receiver);
+        let (a, b): (IpAddr, IpAddr) =
+            ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        let now = Instant::now();
+
+        assert!(limiter.acquire(Some(a), now).is_ok());
+        assert!(limiter.acquire(Some(a), now).is_ok());
+        assert_eq!(limiter.acquire(Some(a), now), Err(Duration::from_secs(1)));
+        assert!(limiter.acquire(Some(b), now).is_ok());
+        assert!(limiter.acquire(Some(a), now + Duration::from_secs(1)).is_ok());
+
+        sender.send_modify(|config| {
+            Arc::make_mut(config).server.rate_limit = None;
+        });
+        for _ in 0..10 {
+            assert!(limiter.acquire(Some(a), now).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_max_clients() {
+        let mut config = crate::config::Config::test_config();
+        config.server.rate_limit = Some(crate::config::RateLimitConfig {
+            requests_per_second: 1,
+            burst: 1,
+        });
+        let (_sender, receiver) = tokio::sync::watch::channel(Arc::new(config));
+        let limiter = super::RateLimiter::new(receiver);
+        let now = Instant::now();
+        let client = |i: usize| Some(IpAddr::from((i as u32).to_be_bytes()));
+
+        // a client seen all along keeps its empty bucket
+        assert!(limiter.acquire(client(0), now).is_ok());
+        for i in 1..super::MAX_CLIENTS * 3 {
+            assert!(limiter.acquire(client(i), now).is_ok());
+            if i % 1000 == 0 {
+                assert!(limiter.acquire(client(0), now).is_err());
+            }
+            assert!(
+                limiter.buckets.lock().unwrap().len() <= super::MAX_CLIENTS
+            );
+        }
+        assert!(limiter.acquire(client(0), now).is_err());
+        // one not seen since is forgotten, starting full again
+        assert!(limiter.acquire(client(1), now).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let mut config = crate::config::Config::test_config();
+        config.server.rate_limit = Some(crate::config::RateLimitConfig {
+            requests_per_second: 1,
+            burst: 1,
+        });
+        let (_sender, receiver) = tokio::sync::watch::channel(Arc::new(config));
+        let router = axum::Router::new()
+            .route("/", axum::routing::get(|| async {}))
+            .layer(axum::middleware::from_fn_with_state(
+                super::RateLimiter::new(receiver),
+                super::rate_limit,
+            ));
+        let request = || {
+            axum::http::Request::get("/")
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+
+        let response = tower::ServiceExt::oneshot(router.clone(), request())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let response =
+            tower::ServiceExt::oneshot(router, request()).await.unwrap();
+        assert_eq!(response.status(), 429);
+        assert_eq!(response.headers()["retry-after"], "1");
+        assert!(super::super::problem::is_problem(response.headers()));
+    }
+}
@@ -20,6 +20,21 @@ fn generate_trace_id() -> String {
     format!("{uid}-{date}")
 }
 
+tokio::task_local! {
+    static TRACE_ID: String;
+}
+
+/// Trace id of the request being served by this task, if any. Tasks
+/// spawned from the request don't inherit it.
+pub fn current() -> Option<String> {
+    TRACE_ID.try_with(|trace_id| trace_id.clone()).ok()
+}
+
+/// Runs `f` with `trace_id` as the [`current`] one.
+pub async fn scope<F: Future>(trace_id: String, f: F) -> F::Output {
+    TRACE_ID.scope(trace_id, f).await
+}
+
 #[derive(Clone, Default)]
 pub struct TraceIdLayer;
 
@@ -66,14 +81,19 @@ where
         let span = info_span!(TRACE_SPAN, trace_id = %trace_id);
         let mut inner = self.inner.clone();
         Box::pin(
-            async move {
-                let mut response = inner.call(req).await?.into_response();
-                if let Ok(value) = HeaderValue::from_str(&trace_id) {
-                    response.headers_mut().insert(TRACE_ID_HEADER_NAME, value);
+            TRACE_ID.scope(
+                trace_id.clone(),
+                async move {
+                    let mut response = inner.call(req).await?.into_response();
+                    if let Ok(value) = HeaderValue::from_str(&trace_id) {
+                        response
+                            .headers_mut()
+                            .insert(TRACE_ID_HEADER_NAME, value);
+                    }
+                    Ok(response)
                 }
-                Ok(response)
-            }
-            .instrument(span),
+                .instrument(span),
+            ),
         )
     }
 }
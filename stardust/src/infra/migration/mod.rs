@@ -0,0 +1,637 @@
+pub mod mysql;
+mod postgres;
+pub mod sqlite;
+
+use std::collections::{BTreeMap, HashSet};
+
+pub use postgres::{Database, Handle, get_latest, init, save};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
+pub struct MigrationEntity {
+    pub name: String,
+    pub version: i32,
+    pub description: String,
+    // None for steps recorded before checksums were tracked
+    #[sqlx(default)]
+    pub checksum: Option<String>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Default for MigrationEntity {
+    fn default() -> Self {
+        Self {
+            name: "".into(),
+            version: 0,
+            description: "".into(),
+            checksum: None,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    // applied, but the step changed since
+    Modified,
+    // applied, but missing from the registry
+    Unknown,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MigrationStatus {
+    pub name: String,
+    pub version: i32,
+    pub description: String,
+    pub state: MigrationState,
+    pub applied_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// sha256 hex digest over the parts making up a migration step.
+pub fn checksum(parts: &[&str]) -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// One versioned step of a module's schema.
+#[async_trait::async_trait]
+pub trait Migration<D: crate::database::Database>: Sync + Send {
+    fn version(&self) -> i32;
+
+    fn description(&self) -> &str;
+
+    /// Recorded when the step is applied; a different value on a later
+    /// start means the step was edited after it ran.
+    fn checksum(&self) -> String;
+
+    async fn up(&self, handle: &mut D::Handle<'_>) -> crate::Result<()>;
+
+    /// Whether `down` can undo this step. Rollbacks refuse to start when a
+    /// step on the way to the target is irreversible.
+    fn reversible(&self) -> bool {
+        false
+    }
+
+    async fn down(&self, _handle: &mut D::Handle<'_>) -> crate::Result<()> {
+        Err(crate::Error::IllegalState(
+            format!("migration v{} is irreversible", self.version()).into(),
+        ))
+    }
+}
+
+/// Persistence of `stardust_migration` for a database backend.
+#[async_trait::async_trait]
+pub trait MigrationStore: crate::database::Database {
+    async fn init_store(&self) -> crate::Result<()>;
+
+    async fn find_migrations(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        name: &str,
+    ) -> crate::Result<Vec<MigrationEntity>>;
+
+    async fn save_migration(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &MigrationEntity,
+    ) -> crate::Result<MigrationEntity>;
+
+    async fn save_checksum(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &MigrationEntity,
+    ) -> crate::Result<()>;
+
+    async fn delete_migration(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &MigrationEntity,
+    ) -> crate::Result<()>;
+}
+
+/// Migration made of plain sql statements, checksummed by the text of its
+/// `statements`. `down` is left out of the checksum so it can be added to
+/// steps that were already applied.
+pub struct SqlMigration {
+    pub version: i32,
+    pub description: &'static str,
+    pub statements: &'static [&'static str],
+    pub down: Option<&'static [&'static str]>,
+}
+
+impl SqlMigration {
+    fn sql_checksum(&self) -> String {
+        checksum(self.statements)
+    }
+}
+
+/// Lock serializing migrations of every process sharing the database, so
+/// replicas starting together don't race on `stardust_migration`.
+pub const LOCK_NAME: &str = "stardust_migration";
+
+/// Waits long enough for another replica to finish a slow migration.
+fn lock_options() -> crate::database::LockOptions {
+    crate::database::LockOptions {
+        timeout: std::time::Duration::from_secs(300),
+        ..Default::default()
+    }
+}
+
+/// Registry of a module's migrations, applied in version order.
+pub struct Migrations<D: crate::database::Database> {
+    name: &'static str,
+    steps: BTreeMap<i32, Box<dyn Migration<D>>>,
+}
+
+impl<D> Migrations<D>
+where
+    D: MigrationStore + 'static,
+{
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            steps: BTreeMap::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Panics on a duplicated or non positive version, both are
+    /// programming errors in the module's registry.
+    pub fn register(mut self, step: impl Migration<D> + 'static) -> Self {
+        let version = step.version();
+        assert!(version > 0, "{}: version must be positive", self.name);
+        assert!(
+            !self.steps.contains_key(&version),
+            "{}: duplicated version {}",
+            self.name,
+            version
+        );
+        self.steps.insert(version, Box::new(step));
+        self
+    }
+
+    /// Applies every pending step under [`LOCK_NAME`], each in its own
+    /// transaction. Refuses to run when an applied step is missing from the
+    /// registry or its checksum changed since it was applied.
+    pub async fn run(&self, database: &D) -> crate::Result<()> {
+        let lock = database.lock(LOCK_NAME, &lock_options()).await?;
+        let result = self.apply(database).await;
+        crate::database::Lock::release(lock).await?;
+        result
+    }
+
+    async fn apply(&self, database: &D) -> crate::Result<()> {
+        database.init_store().await?;
+        let applied = self.verify(database).await?;
+
+        for (version, step) in &self.steps {
+            if applied.contains(version) {
+                continue;
+            }
+            let mut handle = database.tx_handle().await?;
+            step.up(&mut handle).await?;
+            database
+                .save_migration(
+                    &mut handle,
+                    &MigrationEntity {
+                        name: self.name.into(),
+                        version: *version,
+                        description: step.description().into(),
+                        checksum: Some(step.checksum()),
+                        updated_at: chrono::Utc::now(),
+                    },
+                )
+                .await?;
+            crate::database::Handle::commit(handle).await?;
+            tracing::info!(
+                "migration {} v{} applied: {}",
+                self.name,
+                version,
+                step.description()
+            );
+        }
+        Ok(())
+    }
+
+    /// Reverts applied steps above `target` in descending version order
+    /// under [`LOCK_NAME`], each in its own transaction, removing their
+    /// `stardust_migration` records.
+    pub async fn rollback(
+        &self,
+        database: &D,
+        target: i32,
+    ) -> crate::Result<()> {
+        let lock = database.lock(LOCK_NAME, &lock_options()).await?;
+        let result = self.revert(database, target).await;
+        crate::database::Lock::release(lock).await?;
+        result
+    }
+
+    async fn revert(&self, database: &D, target: i32) -> crate::Result<()> {
+        database.init_store().await?;
+        let applied = self.verify(database).await?;
+        let pending: Vec<_> = self
+            .steps
+            .iter()
+            .rev()
+            .filter(|(version, _)| {
+                **version > target && applied.contains(version)
+            })
+            .collect();
+        if let Some((version, _)) =
+            pending.iter().find(|(_, step)| !step.reversible())
+        {
+            return Err(crate::Error::IllegalState(
+                format!(
+                    "migration {} v{} is irreversible, cannot roll back to v{}",
+                    self.name, version, target
+                )
+                .into(),
+            ));
+        }
+
+        for (version, step) in pending {
+            let mut handle = database.tx_handle().await?;
+            step.down(&mut handle).await?;
+            database
+                .delete_migration(
+                    &mut handle,
+                    &MigrationEntity {
+                        name: self.name.into(),
+                        version: *version,
+                        description: step.description().into(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            crate::database::Handle::commit(handle).await?;
+            tracing::info!(
+                "migration {} v{} rolled back: {}",
+                self.name,
+                version,
+                step.description()
+            );
+        }
+        Ok(())
+    }
+
+    /// Every registered or recorded step of this module, in version order.
+    pub async fn status(
+        &self,
+        database: &D,
+    ) -> crate::Result<Vec<MigrationStatus>> {
+        database.init_store().await?;
+        let history =
+            database.find_migrations(&mut database.handle(), self.name).await?;
+        let mut status: BTreeMap<i32, MigrationStatus> = self
+            .steps
+            .iter()
+            .map(|(version, step)| {
+                let status = MigrationStatus {
+                    name: self.name.into(),
+                    version: *version,
+                    description: step.description().into(),
+                    state: MigrationState::Pending,
+                    applied_at: None,
+                };
+                (*version, status)
+            })
+            .collect();
+        for entity in history {
+            let state = match self.steps.get(&entity.version) {
+                None => MigrationState::Unknown,
+                Some(step)
+                    if entity
+                        .checksum
+                        .as_ref()
+                        .is_some_and(|c| *c != step.checksum()) =>
+                {
+                    MigrationState::Modified
+                }
+                Some(_) => MigrationState::Applied,
+            };
+            status.insert(
+                entity.version,
+                MigrationStatus {
+                    name: entity.name,
+                    version: entity.version,
+                    description: entity.description,
+                    state,
+                    applied_at: Some(entity.updated_at),
+                },
+            );
+        }
+        Ok(status.into_values().collect())
+    }
+
+    async fn verify(&self, database: &D) -> crate::Result<HashSet<i32>> {
+        let mut handle = database.handle();
+        let history = database.find_migrations(&mut handle, self.name).await?;
+        let mut applied = HashSet::new();
+        for entity in history {
+            let Some(step) = self.steps.get(&entity.version) else {
+                return Err(crate::Error::IllegalState(
+                    format!(
+                        "migration {} v{} is applied but not registered",
+                        self.name, entity.version
+                    )
+                    .into(),
+                ));
+            };
+            let checksum = step.checksum();
+            match &entity.checksum {
+                Some(recorded) if *recorded != checksum => {
+                    return Err(crate::Error::IllegalState(
+                        format!(
+                            "migration {} v{} was modified after it was applied",
+                            self.name, entity.version
+                        )
+                        .into(),
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    database
+                        .save_checksum(
+                            &mut handle,
+                            &MigrationEntity {
+                                checksum: Some(checksum),
+                                ..entity.clone()
+                            },
+                        )
+                        .await?;
+                }
+            }
+            applied.insert(entity.version);
+        }
+        Ok(applied)
+    }
+}
+
+/// Runs the registries of every module against one database.
+pub struct Migrator<D: crate::database::Database> {
+    database: D,
+    modules: Vec<Migrations<D>>,
+}
+
+impl<D> Migrator<D>
+where
+    D: MigrationStore + 'static,
+{
+    pub fn new(database: D) -> Self {
+        Self {
+            database,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Modules are migrated in registration order, so register a module
+    /// after the ones its schema depends on.
+    pub fn module(mut self, migrations: Migrations<D>) -> Self {
+        self.modules.push(migrations);
+        self
+    }
+
+    pub async fn run(&self) -> crate::Result<()> {
+        for migrations in &self.modules {
+            migrations.run(&self.database).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn status(&self) -> crate::Result<Vec<MigrationStatus>> {
+        let mut status = Vec::new();
+        for migrations in &self.modules {
+            status.extend(migrations.status(&self.database).await?);
+        }
+        Ok(status)
+    }
+
+    pub async fn rollback(&self, name: &str, target: i32) -> crate::Result<()> {
+        let Some(migrations) = self.modules.iter().find(|m| m.name() == name)
+        else {
+            return Err(crate::Error::NotFound(
+                format!("migration {}", name).into(),
+            ));
+        };
+        migrations.rollback(&self.database, target).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::Database as _;
+
+    use super::{MigrationState, Migrations, SqlMigration, sqlite};
+
+    fn registry(
+        statements: &'static [&'static str],
+    ) -> Migrations<sqlite::Database> {
+        Migrations::new("test_migration")
+            .register(SqlMigration {
+                version: 1,
+                description: "create sample",
+                statements: &["CREATE TABLE sample (id INTEGER PRIMARY KEY)"],
+                down: Some(&["DROP TABLE sample"]),
+            })
+            .register(SqlMigration {
+                version: 2,
+                description: "seed sample",
+                statements,
+                down: Some(&["DELETE FROM sample"]),
+            })
+    }
+
+    async fn count(database: &sqlite::Database, sql: &str) -> i64 {
+        let row: (i64,) = sqlx::query_as(sql)
+            .fetch_one(database.handle().executor())
+            .await
+            .unwrap();
+        row.0
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_runs() {
+        let database = crate::testing::database().await.unwrap();
+        let migrations = registry(&["INSERT INTO sample (id) VALUES (1)"]);
+        let (first, second) =
+            tokio::join!(migrations.run(&database), migrations.run(&database));
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(count(&database, "SELECT count(*) FROM sample").await, 1);
+        assert_eq!(
+            count(&database, "SELECT count(*) FROM stardust_lock").await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations() {
+        let database = crate::testing::database().await.unwrap();
+        let migrations = registry(&["INSERT INTO sample (id) VALUES (1)"]);
+        migrations.run(&database).await.unwrap();
+        // applied steps are skipped
+        migrations.run(&database).await.unwrap();
+        assert_eq!(count(&database, "SELECT count(*) FROM sample").await, 1);
+        let history =
+            sqlite::find_all(&mut database.handle(), "test_migration")
+                .await
+                .unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|m| m.checksum.is_some()));
+
+        // edited after it was applied
+        let edited = registry(&["INSERT INTO sample (id) VALUES (2)"]);
+        assert!(matches!(
+            edited.run(&database).await,
+            Err(crate::Error::IllegalState(_))
+        ));
+
+        // applied by a newer build
+        let older = Migrations::<sqlite::Database>::new("test_migration")
+            .register(SqlMigration {
+                version: 1,
+                description: "create sample",
+                statements: &["CREATE TABLE sample (id INTEGER PRIMARY KEY)"],
+                down: None,
+            });
+        assert!(matches!(
+            older.run(&database).await,
+            Err(crate::Error::IllegalState(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_step_rolls_back() {
+        let database = crate::testing::database().await.unwrap();
+        let migrations = registry(&[
+            "INSERT INTO sample (id) VALUES (1)",
+            "INSERT INTO missing (id) VALUES (1)",
+        ]);
+        assert!(migrations.run(&database).await.is_err());
+        assert_eq!(count(&database, "SELECT count(*) FROM sample").await, 0);
+        let history =
+            sqlite::find_all(&mut database.handle(), "test_migration")
+                .await
+                .unwrap();
+        assert_eq!(history.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_checksum_backfilled() {
+        let database = crate::testing::database().await.unwrap();
+        sqlx::query("CREATE TABLE sample (id INTEGER PRIMARY KEY)")
+            .execute(database.handle().executor())
+            .await
+            .unwrap();
+        sqlite::save(
+            &mut database.handle(),
+            &super::MigrationEntity {
+                name: "test_migration".into(),
+                version: 1,
+                description: "create sample".into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let migrations = registry(&["INSERT INTO sample (id) VALUES (1)"]);
+        migrations.run(&database).await.unwrap();
+        let history =
+            sqlite::find_all(&mut database.handle(), "test_migration")
+                .await
+                .unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|m| m.checksum.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_rollback() {
+        let database = crate::testing::database().await.unwrap();
+        let migrator = super::Migrator::new(database.clone())
+            .module(registry(&["INSERT INTO sample (id) VALUES (1)"]));
+        migrator.run().await.unwrap();
+
+        migrator.rollback("test_migration", 1).await.unwrap();
+        assert_eq!(count(&database, "SELECT count(*) FROM sample").await, 0);
+        let history =
+            sqlite::find_all(&mut database.handle(), "test_migration")
+                .await
+                .unwrap();
+        assert_eq!(history.len(), 1);
+
+        migrator.rollback("test_migration", 0).await.unwrap();
+        assert_eq!(
+            count(
+                &database,
+                "SELECT count(*) FROM sqlite_master WHERE name = 'sample'"
+            )
+            .await,
+            0
+        );
+
+        // rolled back steps are applied again
+        migrator.run().await.unwrap();
+        assert_eq!(count(&database, "SELECT count(*) FROM sample").await, 1);
+
+        assert!(matches!(
+            migrator.rollback("missing", 0).await,
+            Err(crate::Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rollback_irreversible() {
+        let database = crate::testing::database().await.unwrap();
+        let migrations = Migrations::<sqlite::Database>::new("test_migration")
+            .register(SqlMigration {
+                version: 1,
+                description: "create sample",
+                statements: &["CREATE TABLE sample (id INTEGER PRIMARY KEY)"],
+                down: None,
+            })
+            .register(SqlMigration {
+                version: 2,
+                description: "seed sample",
+                statements: &["INSERT INTO sample (id) VALUES (1)"],
+                down: Some(&["DELETE FROM sample"]),
+            });
+        migrations.run(&database).await.unwrap();
+        assert!(matches!(
+            migrations.rollback(&database, 0).await,
+            Err(crate::Error::IllegalState(_))
+        ));
+        // nothing is reverted when the rollback can't reach its target
+        assert_eq!(count(&database, "SELECT count(*) FROM sample").await, 1);
+        migrations.rollback(&database, 1).await.unwrap();
+        assert_eq!(count(&database, "SELECT count(*) FROM sample").await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let database = crate::testing::database().await.unwrap();
+        let migrator = super::Migrator::new(database.clone())
+            .module(registry(&["INSERT INTO sample (id) VALUES (1)"]));
+        let status = migrator.status().await.unwrap();
+        assert_eq!(status.len(), 2);
+        assert!(status.iter().all(|s| s.state == MigrationState::Pending));
+
+        migrator.run().await.unwrap();
+        let edited = super::Migrator::new(database.clone())
+            .module(registry(&["INSERT INTO sample (id) VALUES (2)"]));
+        let status = edited.status().await.unwrap();
+        assert_eq!(status[0].state, MigrationState::Applied);
+        assert_eq!(status[1].state, MigrationState::Modified);
+        assert!(status[1].applied_at.is_some());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::database::Database as _;
+
+use super::MigrationEntity;
+
+pub type Database = crate::database::internal::postgres::Database;
+pub type Handle<'h> = crate::database::internal::postgres::Handle<'h>;
+
+pub async fn init(database: Database) -> crate::Result<()> {
+    sqlx::query(
+        r#" CREATE TABLE IF NOT EXISTS stardust_migration (
+        name VARCHAR(255) NOT NULL,
+        version INT NOT NULL,
+        description VARCHAR(255) NOT NULL,
+        checksum VARCHAR(64),
+        updated_at TIMESTAMPTZ NOT NULL );"#,
+    )
+    .execute(database.handle().executor())
+    .await
+    .map_err(crate::database::internal::into_error)?;
+    sqlx::query(
+        "ALTER TABLE stardust_migration ADD COLUMN IF NOT EXISTS checksum VARCHAR(64)",
+    )
+    .execute(database.handle().executor())
+    .await
+    .map_err(crate::database::internal::into_error)?;
+    Ok(())
+}
+
+pub async fn get_latest(
+    handle: &mut Handle<'_>,
+    name: &str,
+) -> crate::Result<Option<MigrationEntity>> {
+    let row =
+        sqlx::QueryBuilder::new("SELECT * FROM stardust_migration WHERE ")
+            .push("name = ")
+            .push_bind(name)
+            .push(" ORDER BY version DESC LIMIT 1")
+            .build_query_as::<MigrationEntity>()
+            .fetch_optional(handle.executor())
+            .await
+            .map_err(crate::database::internal::into_error)?;
+    Ok(row)
+}
+
+pub async fn find_all(
+    handle: &mut Handle<'_>,
+    name: &str,
+) -> crate::Result<Vec<MigrationEntity>> {
+    let rows =
+        sqlx::QueryBuilder::new("SELECT * FROM stardust_migration WHERE ")
+            .push("name = ")
+            .push_bind(name)
+            .push(" ORDER BY version")
+            .build_query_as::<MigrationEntity>()
+            .fetch_all(handle.executor())
+            .await
+            .map_err(crate::database::internal::into_error)?;
+    Ok(rows)
+}
+
+pub async fn save(
+    handle: &mut Handle<'_>,
+    entity: &MigrationEntity,
+) -> crate::Result<MigrationEntity> {
+    let mut builder = sqlx::QueryBuilder::new(
+        "INSERT INTO stardust_migration (name, version, description, checksum, updated_at) ",
+    );
+    builder.push_values(std::iter::once(entity), |mut values, model| {
+        values.push_bind(&model.name);
+        values.push_bind(model.version);
+        values.push_bind(&model.description);
+        values.push_bind(&model.checksum);
+        values.push_bind(model.updated_at);
+    });
+    builder.push(" RETURNING name, version, description, checksum, updated_at");
+    let row = builder
+        .build_query_as::<MigrationEntity>()
+        .fetch_one(handle.executor())
+        .await
+        .map_err(crate::database::internal::into_error)?;
+    Ok(row)
+}
+
+pub async fn save_checksum(
+    handle: &mut Handle<'_>,
+    entity: &MigrationEntity,
+) -> crate::Result<()> {
+    sqlx::QueryBuilder::new("UPDATE stardust_migration SET checksum = ")
+        .push_bind(&entity.checksum)
+        .push(" WHERE name = ")
+        .push_bind(&entity.name)
+        .push(" AND version = ")
+        .push_bind(entity.version)
+        .build()
+        .execute(handle.executor())
+        .await
+        .map_err(crate::database::internal::into_error)?;
+    Ok(())
+}
+
+pub async fn delete(
+    handle: &mut Handle<'_>,
+    entity: &MigrationEntity,
+) -> crate::Result<()> {
+    sqlx::QueryBuilder::new("DELETE FROM stardust_migration WHERE name = ")
+        .push_bind(&entity.name)
+        .push(" AND version = ")
+        .push_bind(entity.version)
+        .build()
+        .execute(handle.executor())
+        .await
+        .map_err(crate::database::internal::into_error)?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl super::MigrationStore for Database {
+    async fn init_store(&self) -> crate::Result<()> {
+        init(self.clone()).await
+    }
+
+    async fn find_migrations(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        name: &str,
+    ) -> crate::Result<Vec<MigrationEntity>> {
+        find_all(handle, name).await
+    }
+
+    async fn save_migration(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &MigrationEntity,
+    ) -> crate::Result<MigrationEntity> {
+        save(handle, entity).await
+    }
+
+    async fn save_checksum(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &MigrationEntity,
+    ) -> crate::Result<()> {
+        save_checksum(handle, entity).await
+    }
+
+    async fn delete_migration(
+        &self,
+        handle: &mut Self::Handle<'_>,
+        entity: &MigrationEntity,
+    ) -> crate::Result<()> {
+        delete(handle, entity).await
+    }
+}
+
+#[async_trait::async_trait]
+impl super::Migration<Database> for super::SqlMigration {
+    fn version(&self) -> i32 {
+        self.version
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn checksum(&self) -> String {
+        self.sql_checksum()
+    }
+
+    async fn up(&self, handle: &mut Handle<'_>) -> crate::Result<()> {
+        execute(handle, self.statements).await
+    }
+
+    fn reversible(&self) -> bool {
+        self.down.is_some()
+    }
+
+    async fn down(&self, handle: &mut Handle<'_>) -> crate::Result<()> {
+        match self.down {
+            Some(statements) => execute(handle, statements).await,
+            None => Err(crate::Error::IllegalState(
+                format!("migration v{} is irreversible", self.version).into(),
+            )),
+        }
+    }
+}
+
+async fn execute(
+    handle: &mut Handle<'_>,
+    statements: &[&str],
+) -> crate::Result<()> {
+    for statement in statements {
+        sqlx::query(statement)
+            .execute(handle.executor())
+            .await
+            .map_err(crate::database::internal::into_error)?;
+    }
+    Ok(())
+}
@@ -8,5 +8,7 @@ pub use error::*;
 pub mod http;
 pub mod grpc;
 pub mod infra;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod with;
 // pub use with::*;
@@ -0,0 +1,371 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::config::{
+    LoggingConfig, LoggingFormat, LoggingRotation, LoggingSinkConfig,
+    LoggingSinkKind,
+};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt;
+use tracing_subscriber::layer::Layered;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry, layer::SubscriberExt, reload,
+};
+
+mod rolling;
+pub use rolling::*;
+
+type Filtered = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+type Sink = Box<dyn Layer<Filtered> + Send + Sync>;
+
+static LOGGING_INIT: Mutex<Option<Vec<WorkerGuard>>> = Mutex::new(None);
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+static PENDING: Mutex<Pending> = Mutex::new(Pending {
+    generation: 0,
+    revert: None,
+});
+
+// every change bumps `generation`, so a revert only fires if nothing
+// replaced the filter in the meantime
+struct Pending {
+    generation: u64,
+    revert: Option<(String, chrono::DateTime<chrono::Utc>)>,
+}
+
+/// The filter in use and the one it goes back to, if it was set with
+/// `set_filter_for`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FilterStatus {
+    pub filter: String,
+    pub revert_to: Option<String>,
+    pub revert_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Installs the global subscriber writing to every sink of `config`.
+/// Later calls do nothing.
+pub fn init(config: &LoggingConfig) -> crate::Result<()> {
+    let mut init = LOGGING_INIT.lock().unwrap();
+    if init.is_some() {
+        return Ok(());
+    }
+    let default_sinks = [LoggingSinkConfig {
+        kind: LoggingSinkKind::Stdout,
+        format: LoggingFormat::Full,
+        filter: None,
+        directory: None,
+        filename: None,
+        rotation: LoggingRotation::default(),
+        max_bytes: None,
+        max_files: None,
+    }];
+    let sinks = match config.sinks.is_empty() {
+        true => &default_sinks[..],
+        false => &config.sinks[..],
+    };
+    let mut layers = Vec::with_capacity(sinks.len());
+    let mut guards = Vec::with_capacity(sinks.len());
+    for sink in sinks {
+        let (writer, guard) =
+            tracing_appender::non_blocking::NonBlockingBuilder::default()
+                .buffered_lines_limit(256_000)
+                .lossy(true) // drop if buffer full
+                .finish(writer(sink)?);
+        layers.push(layer(sink, writer)?);
+        guards.push(guard);
+    }
+
+    let (filter, handle) = reload::Layer::new(
+        // RUST_LOG 환경 변수 확인
+        EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(config.filter.as_str())),
+    );
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layers)
+        .try_init()
+        .map_err(|e| anyhow::anyhow!("logging init failed: {}", e))?;
+    let _ = FILTER.set(handle);
+    *init = Some(guards);
+    Ok(())
+}
+
+fn writer(sink: &LoggingSinkConfig) -> crate::Result<Box<dyn Write + Send>> {
+    let (directory, filename) = match sink.kind {
+        LoggingSinkKind::Stdout => return Ok(Box::new(std::io::stdout())),
+        LoggingSinkKind::Stderr => return Ok(Box::new(std::io::stderr())),
+        LoggingSinkKind::File => (
+            sink.directory.as_deref().unwrap_or("."),
+            sink.filename.as_deref().unwrap_or("app"),
+        ),
+    };
+    let file_error = |e: &dyn std::fmt::Display| {
+        anyhow::anyhow!("open log file {}/{}: {}", directory, filename, e)
+    };
+    let rotation = match sink.rotation {
+        LoggingRotation::Minutely => Rotation::MINUTELY,
+        LoggingRotation::Hourly => Rotation::HOURLY,
+        LoggingRotation::Daily => Rotation::DAILY,
+        LoggingRotation::Never => Rotation::NEVER,
+        LoggingRotation::Size => {
+            return Ok(Box::new(
+                SizeRollingWriter::new(
+                    directory,
+                    filename,
+                    sink.max_bytes.unwrap_or(u64::MAX),
+                    sink.max_files,
+                )
+                .map_err(|e| file_error(&e))?,
+            ));
+        }
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(filename);
+    if let Some(max_files) = sink.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+    Ok(Box::new(
+        builder.build(directory).map_err(|e| file_error(&e))?,
+    ))
+}
+
+fn layer(sink: &LoggingSinkConfig, writer: NonBlocking) -> crate::Result<Sink> {
+    let layer = fmt::layer()
+        .with_writer(writer)
+        .with_ansi(sink.kind != LoggingSinkKind::File);
+    let layer = match sink.format {
+        LoggingFormat::Full => layer.boxed(),
+        LoggingFormat::Compact => layer.compact().boxed(),
+        LoggingFormat::Pretty => layer.pretty().boxed(),
+        LoggingFormat::Json => layer.json().boxed(),
+    };
+    let Some(filter) = &sink.filter else {
+        return Ok(layer);
+    };
+    let filter = EnvFilter::try_new(filter).map_err(|e| {
+        anyhow::anyhow!("logging sink filter {}: {}", filter, e)
+    })?;
+    Ok(layer.with_filter(filter).boxed())
+}
+
+/// Replaces the filter of the subscriber installed by `init`, keeping the
+/// current one when `filter` doesn't parse. Cancels a pending revert.
+pub fn set_filter(filter: &str) -> crate::Result<()> {
+    let mut pending = PENDING.lock().unwrap();
+    reload_filter(filter)?;
+    pending.generation += 1;
+    pending.revert = None;
+    Ok(())
+}
+
+/// `set_filter`, going back to the previous filter after `revert_after`
+/// unless it's changed again before then. Setting another temporary
+/// filter keeps the revert target of the first one. Must be called from
+/// a tokio runtime.
+pub fn set_filter_for(
+    filter: &str,
+    revert_after: Duration,
+) -> crate::Result<FilterStatus> {
+    let revert_at = chrono::Duration::from_std(revert_after)
+        .ok()
+        .and_then(|after| chrono::Utc::now().checked_add_signed(after))
+        .ok_or_else(|| {
+            crate::Error::InvalidParameter(
+                crate::ErrorDetail::new(
+                    "logging.invalid_revert_after",
+                    "revert_after is too long",
+                )
+                .with_field(
+                    "revert_after",
+                    "out_of_range",
+                    "too long",
+                ),
+            )
+        })?;
+    let mut pending = PENDING.lock().unwrap();
+    let previous = current_filter()?;
+    reload_filter(filter)?;
+    pending.generation += 1;
+    let revert_to = match pending.revert.take() {
+        Some((revert_to, _)) => revert_to,
+        None => previous,
+    };
+    pending.revert = Some((revert_to.clone(), revert_at));
+
+    let generation = pending.generation;
+    tokio::spawn(async move {
+        tokio::time::sleep(revert_after).await;
+        let mut pending = PENDING.lock().unwrap();
+        if pending.generation != generation {
+            return;
+        }
+        match reload_filter(&revert_to) {
+            Ok(()) => {
+                tracing::info!("logging: filter reverted to {}", revert_to)
+            }
+            Err(e) => tracing::error!("logging: revert failed: {}", e),
+        }
+        pending.generation += 1;
+        pending.revert = None;
+    });
+    Ok(FilterStatus {
+        filter: filter.to_owned(),
+        revert_to: pending.revert.as_ref().map(|(filter, _)| filter.clone()),
+        revert_at: Some(revert_at),
+    })
+}
+
+/// The filter of the subscriber installed by `init`.
+pub fn filter_status() -> crate::Result<FilterStatus> {
+    let pending = PENDING.lock().unwrap();
+    let (revert_to, revert_at) = match &pending.revert {
+        Some((revert_to, revert_at)) => {
+            (Some(revert_to.clone()), Some(*revert_at))
+        }
+        None => (None, None),
+    };
+    Ok(FilterStatus {
+        filter: current_filter()?,
+        revert_to,
+        revert_at,
+    })
+}
+
+fn handle() -> crate::Result<&'static reload::Handle<EnvFilter, Registry>> {
+    FILTER.get().ok_or_else(|| {
+        crate::Error::IllegalState("logging not initialized".into())
+    })
+}
+
+fn current_filter() -> crate::Result<String> {
+    handle()?
+        .with_current(|filter| filter.to_string())
+        .map_err(|e| anyhow::anyhow!("read filter failed: {:?}", e).into())
+}
+
+fn reload_filter(filter: &str) -> crate::Result<()> {
+    let filter = EnvFilter::try_new(filter).map_err(|e| {
+        crate::Error::InvalidParameter(
+            crate::ErrorDetail::new("logging.invalid_filter", e.to_string())
+                .with_field("filter", "invalid_format", e.to_string()),
+        )
+    })?;
+    handle()?
+        .reload(filter)
+        .map_err(|e| anyhow::anyhow!("reload filter failed: {:?}", e))?;
+    Ok(())
+}
+
+/// Applies `logging.filter` of every config published on `config`.
+pub fn follow(
+    mut config: tokio::sync::watch::Receiver<Arc<crate::config::Config>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut current = config.borrow_and_update().logging.filter.clone();
+        while config.changed().await.is_ok() {
+            let filter = config.borrow_and_update().logging.filter.clone();
+            if filter == current {
+                continue;
+            }
+            match set_filter(&filter) {
+                Ok(()) => tracing::info!("logging: filter set to {}", filter),
+                Err(e) => tracing::error!("logging: {}", e),
+            }
+            current = filter;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn inner_span() {
+        tracing::info!("inside inner span");
+        tracing::error!("inside inner span");
+    }
+
+    fn default_config() -> LoggingConfig {
+        LoggingConfig {
+            filter: "debug".into(),
+            sinks: vec![LoggingSinkConfig {
+                kind: LoggingSinkKind::Stdout,
+                format: LoggingFormat::Json,
+                filter: None,
+                directory: None,
+                filename: None,
+                rotation: LoggingRotation::default(),
+                max_bytes: None,
+                max_files: None,
+            }],
+            format: None,
+            file: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_logging() {
+        init(&default_config()).unwrap();
+        tracing::debug!("debug");
+        tracing::info!("info");
+        tracing::warn!("warn");
+        tracing::error!("error");
+
+        let span = tracing::info_span!("my_span", foo = 3);
+        let _enter = span.enter();
+        inner_span().await;
+        tracing::info!("after inner span");
+    }
+
+    #[tokio::test]
+    async fn test_nested_span() {
+        init(&default_config()).unwrap();
+
+        let span = tracing::info_span!("my_span", foo = 3);
+        let _enter = span.enter();
+        tracing::debug!("my_span");
+
+        let child_span = tracing::info_span!("my_span", foo = 4);
+        let _enter2 = child_span.enter();
+        tracing::debug!("child_span");
+        drop(_enter2);
+        tracing::debug!("my_span");
+    }
+
+    #[tokio::test]
+    async fn test_set_filter() {
+        init(&default_config()).unwrap();
+        set_filter("debug").unwrap();
+        let error = set_filter("stardust=loud").unwrap_err();
+        assert_eq!(error.code(), "logging.invalid_filter");
+        assert_eq!(filter_status().unwrap().filter, "debug");
+
+        // a second temporary filter keeps the first revert target
+        set_filter_for("info", Duration::from_millis(50)).unwrap();
+        let status =
+            set_filter_for("stardust=trace", Duration::from_millis(50))
+                .unwrap();
+        assert_eq!(status.filter, "stardust=trace");
+        assert_eq!(status.revert_to.as_deref(), Some("debug"));
+        assert_eq!(filter_status().unwrap(), status);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let status = filter_status().unwrap();
+        assert_eq!(status.filter, "debug");
+        assert_eq!(status.revert_to, None);
+
+        // out of range, leaving the filter alone
+        let error = set_filter_for("info", Duration::MAX).unwrap_err();
+        assert_eq!(error.code(), "logging.invalid_revert_after");
+        assert_eq!(filter_status().unwrap().filter, "debug");
+
+        // a later change cancels the revert
+        set_filter_for("info", Duration::from_millis(50)).unwrap();
+        set_filter("warn").unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(filter_status().unwrap().filter, "warn");
+        set_filter("debug").unwrap();
+    }
+}
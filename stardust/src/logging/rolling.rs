@@ -0,0 +1,128 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Appends to `<directory>/<filename>` and, once a write would take it
+/// past `max_bytes`, renames it to `<filename>.1`, shifting older files up
+/// by one. Past `max_files`, counting the current file, the oldest go;
+/// `Some(0)` keeps only the current file, like `Some(1)`.
+pub struct SizeRollingWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: Option<usize>,
+    file: File,
+    written: u64,
+}
+
+impl SizeRollingWriter {
+    pub fn new(
+        directory: impl AsRef<Path>,
+        filename: &str,
+        max_bytes: u64,
+        max_files: Option<usize>,
+    ) -> io::Result<Self> {
+        std::fs::create_dir_all(&directory)?;
+        let path = directory.as_ref().join(filename);
+        let file = open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn rolled(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn roll(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let mut count = 0;
+        while self.rolled(count + 1).exists() {
+            count += 1;
+        }
+        let keep =
+            self.max_files.map_or(usize::MAX, |max| max.saturating_sub(1));
+        for index in (1..=count).rev() {
+            match index >= keep {
+                true => std::fs::remove_file(self.rolled(index))?,
+                false => {
+                    std::fs::rename(self.rolled(index), self.rolled(index + 1))?
+                }
+            }
+        }
+        match keep {
+            0 => std::fs::remove_file(&self.path)?,
+            _ => std::fs::rename(&self.path, self.rolled(1))?,
+        }
+        self.file = open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRollingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // a line never spans two files, the non-blocking worker writes
+        // whole lines
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes
+        {
+            self.roll()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_rolling() {
+        let dir = std::env::temp_dir()
+            .join(format!("stardust-rolling-{}", uuid::Uuid::new_v4()));
+        let mut writer =
+            SizeRollingWriter::new(&dir, "app.log", 10, Some(3)).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+        assert_eq!(read("app.log").as_deref(), Some("fourth\n"));
+        assert_eq!(read("app.log.1").as_deref(), Some("third\n"));
+        assert_eq!(read("app.log.2").as_deref(), Some("second\n"));
+        assert_eq!(read("app.log.3"), None);
+
+        // reopened files keep counting from their size
+        let mut writer =
+            SizeRollingWriter::new(&dir, "app.log", 10, None).unwrap();
+        writer.write_all(b"fifth\n").unwrap();
+        assert_eq!(read("app.log").as_deref(), Some("fifth\n"));
+        assert_eq!(read("app.log.3").as_deref(), Some("second\n"));
+
+        let mut writer =
+            SizeRollingWriter::new(&dir, "only.log", 10, Some(0)).unwrap();
+        for line in ["first\n", "second\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        assert_eq!(read("only.log").as_deref(), Some("second\n"));
+        assert_eq!(read("only.log.1"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
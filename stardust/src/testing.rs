@@ -0,0 +1,131 @@
+//! Hermetic test harness backed by an in-memory sqlite database.
+//!
+//! ```ignore
+//! let (database, container) =
+//!     stardust::testing::ContainerBuilder::new(TestContainer::new)
+//!         .migrate(|database, container| {
+//!             module_user::infra::sqlite::migration::migrate(
+//!                 database,
+//!                 container.password_hasher.clone(),
+//!             )
+//!         })
+//!         .build()
+//!         .await?;
+//! ```
+use std::sync::Arc;
+
+use futures_core::future::BoxFuture;
+
+pub type Database = crate::database::internal::sqlite::Database;
+
+type Migration<C> =
+    Box<dyn FnOnce(Database, Arc<C>) -> BoxFuture<'static, crate::Result<()>>>;
+
+/// Opens a fresh `sqlite::memory:` database with the migration table
+/// created. The pool keeps a single connection alive for its whole
+/// lifetime, since every new connection would see an empty database.
+pub async fn database() -> crate::Result<Database> {
+    let pool = sqlx::pool::PoolOptions::<sqlx::Sqlite>::new()
+        .min_connections(1)
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .map_err(crate::database::internal::into_error)?;
+    let database = Database {
+        pool,
+        metrics: Default::default(),
+        retry: Default::default(),
+        slow_query: None,
+    };
+    crate::infra::migration::sqlite::init(database.clone()).await?;
+    Ok(database)
+}
+
+pub struct ContainerBuilder<C> {
+    factory: Box<dyn FnOnce(Database) -> C>,
+    migrations: Vec<Migration<C>>,
+}
+
+impl<C> ContainerBuilder<C>
+where
+    C: Send + Sync + 'static,
+{
+    pub fn new(factory: impl FnOnce(Database) -> C + 'static) -> Self {
+        Self {
+            factory: Box::new(factory),
+            migrations: Vec::new(),
+        }
+    }
+
+    /// Adds a module migration, run in the order they were added.
+    pub fn migrate<F, Fut>(mut self, migration: F) -> Self
+    where
+        F: FnOnce(Database, Arc<C>) -> Fut + 'static,
+        Fut: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        self.migrations.push(Box::new(move |database, container| {
+            Box::pin(migration(database, container))
+        }));
+        self
+    }
+
+    pub async fn build(self) -> crate::Result<(Database, Arc<C>)> {
+        let database = database().await?;
+        let container = Arc::new((self.factory)(database.clone()));
+        for migration in self.migrations {
+            migration(database.clone(), container.clone()).await?;
+        }
+        Ok((database, container))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::Database as _;
+
+    struct TestContainer {
+        database: super::Database,
+    }
+
+    #[tokio::test]
+    async fn test_container_builder() {
+        let (database, container) =
+            super::ContainerBuilder::new(|database| TestContainer { database })
+                .migrate(|database, _| async move {
+                    sqlx::query("CREATE TABLE sample (id INTEGER PRIMARY KEY)")
+                        .execute(database.handle().executor())
+                        .await
+                        .map_err(crate::database::internal::into_error)?;
+                    Ok(())
+                })
+                .migrate(|_, container| async move {
+                    sqlx::query("INSERT INTO sample (id) VALUES (1)")
+                        .execute(container.database.handle().executor())
+                        .await
+                        .map_err(crate::database::internal::into_error)?;
+                    Ok(())
+                })
+                .build()
+                .await
+                .unwrap();
+
+        let row: (i64,) = sqlx::query_as("SELECT count(*) FROM sample")
+            .fetch_one(database.handle().executor())
+            .await
+            .unwrap();
+        assert_eq!(row.0, 1);
+        drop(container);
+
+        // every harness starts from an empty database
+        let database = super::database().await.unwrap();
+        let row: (i64,) = sqlx::query_as(
+            "SELECT count(*) FROM sqlite_master WHERE name = 'sample'",
+        )
+        .fetch_one(database.handle().executor())
+        .await
+        .unwrap();
+        assert_eq!(row.0, 0);
+    }
+}